@@ -0,0 +1,314 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use std::fmt;
+
+use crate::consts::{
+    BACKUP_FORMAT_VERSION, BACKUP_MAGIC, BACKUP_MAX_MEMORY_COST, BACKUP_MAX_PARALLELISM,
+    BACKUP_MAX_TIME_COST, CSV_FOLDER, CSV_KIND, CSV_NOTES, CSV_PASSWORD, CSV_PLACE, CSV_TAGS,
+    CSV_TOTP, CSV_USERNAME, NONCE_LENGTH, TAG_SEPARATOR,
+};
+use crate::csv;
+use crate::errors::Error;
+use crate::password::{field_headers, CustomField, Password};
+use crate::utils::generate_salt;
+
+const KDF_NAME: &str = "argon2id";
+const BACKUP_COLUMNS: [&str; 8] = [
+    CSV_PLACE,
+    CSV_USERNAME,
+    CSV_PASSWORD,
+    CSV_KIND,
+    CSV_FOLDER,
+    CSV_TAGS,
+    CSV_NOTES,
+    CSV_TOTP,
+];
+
+/// Cleartext header of an encrypted backup. It is authenticated along with the entries, so
+/// changing any of its values makes the backup fail to open.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupHeader {
+    pub version: u32,
+    pub memory_cost: u32,
+    pub time_cost: u32,
+    pub parallelism: u32,
+    pub salt: Vec<u8>,
+    pub entries: usize,
+}
+
+impl BackupHeader {
+    /// Header for a new backup, with a fresh salt and the default Argon2id parameters.
+    pub fn new(entries: usize) -> Self {
+        let params = Params::default();
+
+        Self {
+            version: BACKUP_FORMAT_VERSION,
+            memory_cost: params.m_cost(),
+            time_cost: params.t_cost(),
+            parallelism: params.p_cost(),
+            salt: generate_salt(),
+            entries,
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, Error> {
+        let mut lines = text.lines();
+
+        if lines.next() != Some(BACKUP_MAGIC) {
+            return Err(Error::BadBackup(
+                "not a password manager backup".to_string(),
+            ));
+        }
+
+        let mut values = Vec::new();
+
+        for line in lines {
+            let (name, value) = line.split_once(" = ").ok_or(Error::BadBackup(format!(
+                "malformed header line `{}`",
+                line
+            )))?;
+
+            values.push((name, value));
+        }
+
+        let value = |name: &str| {
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+                .ok_or(Error::BadBackup(format!("the header has no `{}`", name)))
+        };
+        let number = |name: &str| {
+            value(name)?
+                .parse::<u64>()
+                .map_err(|_| Error::BadBackup(format!("`{}` is not a number", name)))
+        };
+        let out_of_range = |name: &str| Error::BadBackup(format!("`{}` is out of range", name));
+        let small_number =
+            |name: &str| u32::try_from(number(name)?).map_err(|_| out_of_range(name));
+        // Deriving the key with whatever the header asks for could exhaust memory or time.
+        let capped = |name: &str, max: u32| {
+            let number = small_number(name)?;
+
+            if number > max {
+                return Err(Error::BadBackup(format!(
+                    "`{}` is above the maximum of {}",
+                    name, max
+                )));
+            }
+
+            Ok(number)
+        };
+
+        let version = small_number("version")?;
+
+        if version != BACKUP_FORMAT_VERSION {
+            return Err(Error::UnsupportedBackup(version));
+        }
+        if value("kdf")? != KDF_NAME {
+            return Err(Error::BadBackup("unsupported key derivation".to_string()));
+        }
+
+        Ok(Self {
+            version,
+            memory_cost: capped("memory_cost", BACKUP_MAX_MEMORY_COST)?,
+            time_cost: capped("time_cost", BACKUP_MAX_TIME_COST)?,
+            parallelism: capped("parallelism", BACKUP_MAX_PARALLELISM)?,
+            salt: BASE64_STANDARD
+                .decode(value("salt")?)
+                .map_err(|_| Error::BadBackup("the salt is not valid base64".to_string()))?,
+            entries: usize::try_from(number("entries")?).map_err(|_| out_of_range("entries"))?,
+        })
+    }
+
+    fn cipher(&self, passphrase: &str) -> Result<Aes256Gcm, Error> {
+        let params = Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(32))
+            .map_err(|err| Error::KeyDerivationError(err.to_string()))?;
+        let mut derived_key = [0u8; 32];
+
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut derived_key)
+            .map_err(|err| Error::KeyDerivationError(err.to_string()))?;
+
+        Ok(Aes256Gcm::new(&derived_key.into()))
+    }
+}
+
+impl fmt::Display for BackupHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", BACKUP_MAGIC)?;
+        write!(f, "\nversion = {}", self.version)?;
+        write!(f, "\nkdf = {}", KDF_NAME)?;
+        write!(f, "\nmemory_cost = {}", self.memory_cost)?;
+        write!(f, "\ntime_cost = {}", self.time_cost)?;
+        write!(f, "\nparallelism = {}", self.parallelism)?;
+        write!(f, "\nsalt = {}", BASE64_STANDARD.encode(&self.salt))?;
+        write!(f, "\nentries = {}", self.entries)
+    }
+}
+
+/// Encrypts decrypted passwords into a backup archive: the header, a blank line, then the
+/// entries as CSV sealed with AES-256-GCM under a key derived from `passphrase`.
+pub fn seal(passwords: &[Password], passphrase: &str) -> Result<String, Error> {
+    let header = BackupHeader::new(passwords.len());
+    let header_text = header.to_string();
+    let contents = to_csv(passwords);
+
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let payload = Payload {
+        msg: contents.as_bytes(),
+        aad: header_text.as_bytes(),
+    };
+    let ciphertext = header
+        .cipher(passphrase)?
+        .encrypt(&nonce, payload)
+        .map_err(|_| Error::EncryptionError)?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+
+    Ok(format!(
+        "{}\n\n{}\n",
+        header_text,
+        BASE64_STANDARD.encode(sealed)
+    ))
+}
+
+/// Decrypts a backup archive made by `seal`, returning its (decrypted) passwords.
+pub fn open(archive: &str, passphrase: &str) -> Result<Vec<Password>, Error> {
+    let (header_text, sealed) = archive
+        .split_once("\n\n")
+        .ok_or(Error::BadBackup("missing header".to_string()))?;
+    let header = BackupHeader::parse(header_text)?;
+
+    let sealed = BASE64_STANDARD
+        .decode(sealed.trim())
+        .map_err(|_| Error::BadBackup("the entries are not valid base64".to_string()))?;
+
+    if sealed.len() < NONCE_LENGTH {
+        return Err(Error::BadBackup("the entries are truncated".to_string()));
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: header_text.as_bytes(),
+    };
+    let contents = header
+        .cipher(passphrase)?
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .map_err(|_| Error::WrongPassphrase)?;
+    let contents = String::from_utf8(contents)
+        .map_err(|_| Error::BadBackup("the entries are not valid UTF-8".to_string()))?;
+
+    let passwords = from_csv(&contents)?;
+
+    if passwords.len() != header.entries {
+        return Err(Error::BadBackup(format!(
+            "expected {} entries, found {}",
+            header.entries,
+            passwords.len()
+        )));
+    }
+
+    Ok(passwords)
+}
+
+/// Decrypted passwords as CSV, one row per password with a column per custom field.
+pub fn to_csv(passwords: &[Password]) -> String {
+    let field_headers = field_headers(passwords);
+    let mut headers = BACKUP_COLUMNS.to_vec();
+    headers.extend(field_headers.iter().map(String::as_str));
+
+    let mut contents = csv::write_record(&headers);
+
+    for password in passwords {
+        let mut cells = vec![
+            password.place.clone(),
+            password.username.clone(),
+            password.password.clone(),
+            (password.kind as i32).to_string(),
+            password.folder.clone(),
+            password.tags.join(TAG_SEPARATOR),
+            password.notes.clone(),
+            password.totp.clone(),
+        ];
+        cells.extend(password.field_cells(&field_headers));
+
+        contents.push_str(&csv::write_record(&cells));
+    }
+
+    contents
+}
+
+/// Reads passwords written by `to_csv`. Only the place, username and password columns are
+/// required.
+pub fn from_csv(contents: &str) -> Result<Vec<Password>, Error> {
+    let mut records = csv::parse(contents)?.into_iter();
+    let headers = match records.next() {
+        Some(header) => header.fields,
+        None => return Ok(Vec::new()),
+    };
+    let index = |name: &str| headers.iter().position(|header| header == name);
+    let required = |name: &str| index(name).ok_or(Error::NoHeader(name.to_string()));
+
+    let place_index = required(CSV_PLACE)?;
+    let username_index = required(CSV_USERNAME)?;
+    let password_index = required(CSV_PASSWORD)?;
+    let kind_index = index(CSV_KIND);
+    let folder_index = index(CSV_FOLDER);
+    let tags_index = index(CSV_TAGS);
+    let notes_index = index(CSV_NOTES);
+    let totp_index = index(CSV_TOTP);
+    let field_columns = headers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, header)| CustomField::from_header(header).map(|field| (index, field)))
+        .collect::<Vec<_>>();
+
+    let mut passwords = Vec::new();
+
+    for record in records {
+        let cell = |index: Option<usize>| {
+            index
+                .and_then(|index| record.fields.get(index))
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let mut password = Password::new(
+            cell(Some(username_index)),
+            cell(Some(place_index)),
+            cell(Some(password_index)),
+        );
+
+        if let Some(kind) = kind_index.map(|index| cell(Some(index))) {
+            password.kind = kind
+                .parse::<i32>()
+                .map_err(|_| Error::ParsingError)?
+                .try_into()?;
+        }
+
+        password.set_folder(&cell(folder_index));
+        password.set_tags(&cell(tags_index).split(TAG_SEPARATOR).collect::<Vec<_>>())?;
+        password.notes = cell(notes_index);
+        password.totp = cell(totp_index);
+
+        for (index, field) in field_columns.iter() {
+            let value = cell(Some(*index));
+
+            if !value.is_empty() {
+                password.set_field(CustomField {
+                    value,
+                    ..field.clone()
+                });
+            }
+        }
+
+        passwords.push(password);
+    }
+
+    Ok(passwords)
+}
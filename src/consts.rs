@@ -1,16 +1,57 @@
 pub const HASH_COST: u32 = 8;
+pub const SALT_LENGTH: usize = 16;
+pub const NONCE_LENGTH: usize = 12;
 pub const LOWERCASE_CHARACTERS: &str = "abcdefghijklmnopqrstuvwxyz";
 pub const NUMBERS: &str = "0123456789";
 pub const SPECIAL_CHARACTERS: &str = "!@#$%^&*()-_=+[]{}<>/?";
+/// EFF's large wordlist for passphrases (https://www.eff.org/dice), licensed under CC BY 3.0 US.
+pub const EFF_WORDLIST: &str = include_str!("wordlists/eff_large_wordlist.txt");
+pub const PASSPHRASE_WORDS: usize = 6;
+pub const PASSPHRASE_SEPARATOR: &str = "-";
+/// Guesses per second assumed when estimating how long a password takes to crack: an offline
+/// attack slowed down by a password hashing function.
+pub const CRACK_GUESSES_PER_SECOND: f64 = 1e4;
+/// Least strength score, from 0 to 4, saved passwords are expected to have.
+pub const DEFAULT_MIN_PASSWORD_SCORE: u8 = 3;
+/// Longest password a `generate --pattern` template may produce.
+pub const PATTERN_MAX_LENGTH: usize = 1024;
 pub const BACKUP_FILE_NAME: &str = "password_backup.csv";
+pub const BACKUP_ARCHIVE_NAME: &str = "password_backup.pmbak";
+pub const BACKUP_MAGIC: &str = "password-manager backup";
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+/// Largest Argon2id parameters a backup header may ask for: 1 GiB of memory (in KiB), 16 passes
+/// and 16 lanes. The header is read before anything is authenticated.
+pub const BACKUP_MAX_MEMORY_COST: u32 = 1024 * 1024;
+pub const BACKUP_MAX_TIME_COST: u32 = 16;
+pub const BACKUP_MAX_PARALLELISM: u32 = 16;
+pub const SNAPSHOT_KEEP_LAST: usize = 10;
+pub const SNAPSHOT_KEEP_DAILY: usize = 7;
 pub const CSV_PLACE: &str = "place";
 pub const CSV_USERNAME: &str = "username";
 pub const CSV_PASSWORD: &str = "password";
 pub const CSV_ENCRYPTED: &str = "encrypted";
+pub const CSV_KIND: &str = "kind";
+pub const CSV_FOLDER: &str = "folder";
+pub const CSV_TAGS: &str = "tags";
+pub const CSV_NOTES: &str = "notes";
+pub const CSV_KDF_SALT: &str = "kdf_salt";
+pub const CSV_TOTP: &str = "totp";
+pub const CSV_FIELD_PREFIX: &str = "field:";
+pub const CSV_CONCEALED_FIELD_PREFIX: &str = "concealed:";
+pub const TAG_SEPARATOR: &str = ";";
+pub const CONCEALED_VALUE: &str = "********";
+pub const TOTP_DEFAULT_DIGITS: u32 = 6;
+pub const TOTP_DEFAULT_PERIOD: u64 = 30;
+pub const TOTP_URI_PREFIX: &str = "otpauth://";
+pub const FIELD_URL: &str = "url";
+pub const IMPORT_UNTITLED: &str = "untitled";
+pub const JSON_FORMAT_NAME: &str = "password-manager";
+pub const JSON_SCHEMA_VERSION: u32 = 1;
 
 pub mod communications {
     pub const WRONG_KEY: &str = "Wrong key! Try again.";
     pub const ENTER_KEY: &str = "Enter key: ";
+    pub const ENTER_NEW_KEY: &str = "Enter new key: ";
     pub const CONFIRM_KEY: &str = "Confirm key: ";
     pub const ERROR_CONFIRMING_KEY: &str = "Your keys do not match!";
     pub const INIT_KEY: &str =
@@ -19,10 +60,77 @@ pub mod communications {
     pub const NEW_PLACE: &str = "New place (leave empty to keep current):";
     pub const NEW_USERNAME: &str = "New username (leave empty to keep current):";
     pub const NEW_PASSWORD: &str = "New password (leave empty to keep current):";
+    pub const NEW_FOLDER: &str = "New folder (leave empty to keep current, `/` to remove):";
+    pub const NEW_NOTES: &str = "New notes (leave empty to keep current, `-` to remove):";
+    pub const EDIT_FIELDS: &str = "Do you want to edit the custom fields?";
+    pub const FIELD_NAME: &str = "Field name (leave empty to finish):";
+    pub const FIELD_VALUE: &str = "Field value (leave empty to remove the field):";
+    pub const CONCEAL_FIELD: &str = "Should the field be concealed?";
+    pub const ENTER_TOTP: &str = "Base32 secret or otpauth:// URI: ";
+    pub const TOTP_SAVED: &str = "One-time password set up.";
+    pub const REMOVE_TOTP_CONFIRMATION: &str =
+        "Are you sure you want to remove the one-time password of this entry?";
+    pub const TOTP_REMOVED: &str = "One-time password removed.";
+    pub const TOTP_VALID_FOR: &str = "valid for";
+    pub const NEW_TAGS: &str =
+        "New tags separated by commas (leave empty to keep current, `,` to remove all):";
     pub const GENERATED_PASSWORD: &str = "Generated Password:";
-    pub const ENTER_PASSWORD: &str = "Enter the password:";
+    pub const ENTER_THE: &str = "Enter the";
+    pub const SKIP_IF_EMPTY: &str = "(leave empty to skip)";
+    pub const ENTER_NOTE: &str = "Enter the note (finish with an empty line):";
+    pub const ENTER_PRIVATE_KEY_FILE: &str = "Path to the private key file:";
     pub const SAVED_PASSWORD: &str = "Saved password:";
     pub const PASSWORD_DELETE_CONFIRMATION: &str = "Are you sure you want to delete this password?";
     pub const YES_NO: &str = "[y/n]";
+    pub const NEVER_USED: &str = "never used";
+    pub const NO_HISTORY: &str = "This password has never been changed.";
+    pub const VERSION: &str = "Version";
+    pub const REPLACED_AT: &str = "replaced";
+    pub const RESTORED_VERSION: &str = "Restored version";
+    pub const MOVED_TO_TRASH: &str = "Moved the password to the trash.";
+    pub const RESTORED_FROM_TRASH: &str = "Restored password:";
+    pub const DELETED_AT: &str = "deleted";
+    pub const TRASH_EMPTY: &str = "Nothing to delete from the trash.";
+    pub const EMPTY_TRASH_CONFIRMATION: &str =
+        "Are you sure you want to permanently delete these passwords? Count:";
+    pub const SELECT_ACCOUNT: &str = "There are several accounts for this place. Select one:";
+    pub const ENTER_CHOICE: &str = "Enter a number:";
     pub const OPERATION_CANCELLED: &str = "Operation cancelled.";
+    pub const ENTER_BACKUP_PASSPHRASE: &str = "Enter a passphrase for the backup: ";
+    pub const CONFIRM_BACKUP_PASSPHRASE: &str = "Confirm the backup passphrase: ";
+    pub const ERROR_CONFIRMING_BACKUP_PASSPHRASE: &str = "The passphrases do not match!";
+    pub const BACKUP_PASSPHRASE: &str = "Enter the backup passphrase: ";
+    pub const BACKED_UP: &str = "Backed up passwords:";
+    pub const RESTORED_BACKUP: &str = "Restored passwords:";
+    pub const SKIPPED_EXISTING: &str = "Skipped passwords that already exist:";
+    pub const ALREADY_EXISTS: &str = "already exists. What should be done with it?";
+    pub const RENAMED_FROM: &str = "renamed from";
+    pub const IMPORT_INSERTED: &str = "Inserted passwords:";
+    pub const IMPORT_OVERWRITTEN: &str = "Overwritten passwords:";
+    pub const IMPORT_SKIPPED: &str = "Skipped passwords:";
+    pub const ENTROPY: &str = "Entropy:";
+    pub const BITS: &str = "bits";
+    pub const STRENGTH: &str = "Strength:";
+    pub const WEAK_PASSWORD: &str = "Warning: the password scores below the minimum of";
+    pub const DRY_RUN: &str = "Dry run, nothing was written.";
+    pub const EXPORTED: &str = "Exported passwords:";
+    pub const SKIPPED_MULTILINE: &str =
+        "Skipped passwords spanning several lines, which pass only keeps the first line of:";
+    pub const ONLY_IN_DUMP: &str = "Only in the dump:";
+    pub const ONLY_IN_VAULT: &str = "Only in the vault:";
+    pub const DIFFERENT_IN_DUMP: &str = "Different in the dump:";
+    pub const DIFF_USERNAME: &str = "username is";
+    pub const DIFF_PASSWORD: &str = "password";
+    pub const DIFF_ENCRYPTION: &str = "encryption";
+    pub const NO_DIFFERENCES: &str = "The vault and the dump hold the same passwords.";
+    pub const NO_PROFILES: &str = "There are no generation profiles yet.";
+    pub const NO_POLICY: &str = "No generation profile is attached to";
+    pub const NO_BACKUPS: &str = "There are no automatic backups yet.";
+    pub const AUTO_BACKUP_FAILED: &str = "Warning: the automatic backup failed.";
+    pub const RESTORE_SNAPSHOT_CONFIRMATION: &str =
+        "Are you sure you want to replace the database with the backup";
+    pub const RESTORED_SNAPSHOT: &str = "Restored the database from the backup";
+    pub const PREVIOUS_DATABASE_SAVED: &str = "The replaced database was saved as the backup";
+    pub const KEY_CHANGED: &str = "Key changed. Re-encrypted passwords:";
+    pub const CRYPTO_MIGRATED: &str = "Upgraded passwords to the current encryption:";
 }
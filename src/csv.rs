@@ -0,0 +1,188 @@
+use std::{borrow::Cow, iter::Peekable, str::Chars};
+
+use crate::errors::Error;
+
+/// Record terminator. Readers accept a bare `\n` as well.
+const LINE_ENDING: &str = "\r\n";
+
+/// One CSV record and the line it starts on, for error reporting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// Character reader keeping track of the current line and column.
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let char = self.chars.next();
+
+        match char {
+            Some('\n') => {
+                self.line += 1;
+                self.column = 1;
+            }
+            Some(_) => self.column += 1,
+            None => (),
+        }
+
+        char
+    }
+
+    fn error(&self, reason: impl Into<String>) -> Error {
+        Error::MalformedCsv(self.line, self.column, reason.into())
+    }
+
+    /// Consumes the record terminator, if any.
+    fn line_ending(&mut self) -> Result<(), Error> {
+        match self.peek() {
+            Some('\n') => {
+                self.bump();
+            }
+            Some('\r') => {
+                let error = self.error("carriage return outside of quotes");
+
+                self.bump();
+
+                if self.bump() != Some('\n') {
+                    return Err(error);
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Quotes a field if it holds a separator, a quote or a line break (RFC 4180).
+pub fn escape_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Formats one record, terminator included.
+pub fn write_record<S: AsRef<str>>(fields: &[S]) -> String {
+    // A lone empty field is quoted so that the record does not read as a blank line.
+    if let [field] = fields {
+        if field.as_ref().is_empty() {
+            return format!("\"\"{}", LINE_ENDING);
+        }
+    }
+
+    let fields = fields
+        .iter()
+        .map(|field| escape_field(field.as_ref()))
+        .collect::<Vec<_>>();
+
+    format!("{}{}", fields.join(","), LINE_ENDING)
+}
+
+/// Parses RFC 4180 CSV. Every record must have as many fields as the first one; malformed input
+/// is reported with the line and column (both starting at 1) where it goes wrong. Blank lines are
+/// skipped, since `write_record` never produces them.
+pub fn parse(input: &str) -> Result<Vec<Record>, Error> {
+    let mut cursor = Cursor::new(input);
+    let mut records: Vec<Record> = Vec::new();
+
+    while cursor.peek().is_some() {
+        if matches!(cursor.peek(), Some('\r') | Some('\n')) {
+            cursor.line_ending()?;
+            continue;
+        }
+
+        let line = cursor.line;
+        let mut fields = Vec::new();
+
+        loop {
+            if let Some(expected) = records.first().map(|record| record.fields.len()) {
+                if fields.len() == expected {
+                    return Err(cursor.error(format!("expected {} fields, found more", expected)));
+                }
+            }
+
+            fields.push(parse_field(&mut cursor)?);
+
+            if cursor.peek() == Some(',') {
+                cursor.bump();
+                continue;
+            }
+
+            if let Some(expected) = records.first().map(|record| record.fields.len()) {
+                if fields.len() < expected {
+                    return Err(cursor.error(format!(
+                        "expected {} fields, found {}",
+                        expected,
+                        fields.len()
+                    )));
+                }
+            }
+
+            cursor.line_ending()?;
+            break;
+        }
+
+        records.push(Record { line, fields });
+    }
+
+    Ok(records)
+}
+
+fn parse_field(cursor: &mut Cursor) -> Result<String, Error> {
+    let mut field = String::new();
+
+    if cursor.peek() == Some('"') {
+        let opening_quote = cursor.error("unterminated quoted field");
+
+        cursor.bump();
+
+        loop {
+            match cursor.bump() {
+                Some('"') if cursor.peek() == Some('"') => {
+                    cursor.bump();
+                    field.push('"');
+                }
+                Some('"') => break,
+                Some(char) => field.push(char),
+                None => return Err(opening_quote),
+            }
+        }
+
+        return match cursor.peek() {
+            None | Some(',') | Some('\r') | Some('\n') => Ok(field),
+            Some(_) => Err(cursor.error("unexpected character after a closing quote")),
+        };
+    }
+
+    loop {
+        match cursor.peek() {
+            None | Some(',') | Some('\r') | Some('\n') => return Ok(field),
+            Some('"') => return Err(cursor.error("quote inside an unquoted field")),
+            Some(char) => {
+                cursor.bump();
+                field.push(char);
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs;
+
+use crate::database::objects::{ConfigItem, ConfigParams};
+use crate::database::queries::DatabaseInterface;
+use crate::database::utils::{create_kdf_salt, get_save_dir_path, get_save_file_path};
+use crate::errors::Error;
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub statements: &'static [&'static str],
+}
+
+/// Every schema change made since 0.4, in the order they have to be applied. A vault without a
+/// schema version is at version 0, the schema created by `create_password_table` and
+/// `create_config_table`.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Rename the `encrypted` flag to `cipher`",
+        statements: &["ALTER TABLE passwords RENAME COLUMN encrypted TO cipher;"],
+    },
+    Migration {
+        version: 2,
+        description: "Key passwords by id to allow several accounts per place",
+        statements: &[
+            "CREATE TABLE passwords_by_id (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                place TEXT NOT NULL,
+                username TEXT NOT NULL,
+                password TEXT NOT NULL,
+                cipher INTEGER NOT NULL,
+                UNIQUE (place, username)
+            );",
+            "INSERT INTO passwords_by_id (place, username, password, cipher)
+                SELECT place, COALESCE(username, ''), COALESCE(password, ''), COALESCE(cipher, 0)
+                FROM passwords;",
+            "DROP TABLE passwords;",
+            "ALTER TABLE passwords_by_id RENAME TO passwords;",
+        ],
+    },
+    Migration {
+        version: 3,
+        description: "Track when passwords are created, updated and last used",
+        statements: &[
+            "ALTER TABLE passwords ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;",
+            "ALTER TABLE passwords ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;",
+            "ALTER TABLE passwords ADD COLUMN last_used_at INTEGER;",
+            "UPDATE passwords SET created_at = unixepoch(), updated_at = unixepoch();",
+        ],
+    },
+    Migration {
+        version: 4,
+        description: "Keep previous values of edited passwords",
+        statements: &["CREATE TABLE password_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            password_id INTEGER NOT NULL REFERENCES passwords (id),
+            password TEXT NOT NULL,
+            cipher INTEGER NOT NULL,
+            replaced_at INTEGER NOT NULL
+        );"],
+    },
+    Migration {
+        version: 5,
+        description: "Move deleted passwords to a trash",
+        statements: &["ALTER TABLE passwords ADD COLUMN deleted_at INTEGER;"],
+    },
+    Migration {
+        version: 6,
+        description: "Organize passwords with folders and tags",
+        statements: &[
+            "ALTER TABLE passwords ADD COLUMN folder TEXT NOT NULL DEFAULT '';",
+            "CREATE TABLE password_tags (
+                password_id INTEGER NOT NULL REFERENCES passwords (id),
+                tag TEXT NOT NULL,
+                PRIMARY KEY (password_id, tag)
+            );",
+        ],
+    },
+    Migration {
+        version: 7,
+        description: "Store notes and custom fields next to passwords",
+        statements: &[
+            "ALTER TABLE passwords ADD COLUMN notes TEXT NOT NULL DEFAULT '';",
+            "CREATE TABLE password_fields (
+                password_id INTEGER NOT NULL REFERENCES passwords (id),
+                name TEXT NOT NULL,
+                value TEXT NOT NULL,
+                concealed INTEGER NOT NULL,
+                PRIMARY KEY (password_id, name)
+            );",
+        ],
+    },
+    Migration {
+        version: 8,
+        description: "Store one-time password secrets next to passwords",
+        statements: &["ALTER TABLE passwords ADD COLUMN totp TEXT NOT NULL DEFAULT '';"],
+    },
+    Migration {
+        version: 9,
+        description: "Add entry types, making every existing entry a login",
+        statements: &["ALTER TABLE passwords ADD COLUMN kind INTEGER NOT NULL DEFAULT 0;"],
+    },
+    Migration {
+        version: KDF_SALT_MIGRATION,
+        description: "Create the key derivation salt",
+        statements: &[],
+    },
+];
+
+/// The migration that stores a random key derivation salt. It can't be written in SQL, so
+/// `apply_migration` creates the salt itself.
+const KDF_SALT_MIGRATION: u32 = 10;
+
+/// Tables that may exist in a vault at the latest schema version.
+pub const KNOWN_TABLES: &[&str] = &[
+    "passwords",
+    "config",
+    "sqlite_sequence",
+    "password_history",
+    "password_tags",
+    "password_fields",
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+pub async fn get_schema_version(conn: &mut DatabaseInterface) -> Result<u32, Error> {
+    if !conn.has_setting(ConfigParams::SchemaVersion).await? {
+        return Ok(0);
+    }
+
+    conn.get_setting(ConfigParams::SchemaVersion)
+        .await?
+        .value
+        .parse::<u32>()
+        .map_err(|_| Error::ParsingError)
+}
+
+/// Applies every pending migration, each in its own transaction. When `backup` is set the save
+/// file is copied next to itself before anything is changed.
+pub async fn migrate(conn: &mut DatabaseInterface, backup: bool) -> Result<(), Error> {
+    let current_version = get_schema_version(conn).await?;
+
+    if current_version > latest_version() {
+        return Err(Error::UnsupportedSchema(current_version));
+    }
+
+    let pending = MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > current_version)
+        .collect::<Vec<_>>();
+
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    if backup {
+        backup_save_file(current_version)?;
+    }
+
+    for migration in pending {
+        conn.begin().await?;
+
+        match apply_migration(conn, migration).await {
+            Ok(_) => conn.commit().await?,
+            Err(err) => {
+                conn.rollback().await?;
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn apply_migration(conn: &mut DatabaseInterface, migration: &Migration) -> Result<(), Error> {
+    for statement in migration.statements {
+        conn.execute(statement).await.map_err(|err| {
+            Error::MigrationError(migration.version, migration.description, err.to_string())
+        })?;
+    }
+
+    if migration.version == KDF_SALT_MIGRATION {
+        create_kdf_salt(conn).await?;
+    }
+
+    let setting = ConfigItem {
+        name: ConfigParams::SchemaVersion,
+        value: migration.version.to_string(),
+    };
+
+    conn.update_setting(setting).await
+}
+
+fn backup_save_file(version: u32) -> Result<(), Error> {
+    let backup_path = get_save_dir_path()?.join(format!("data.v{}.sqlite.bak", version));
+
+    fs::copy(get_save_file_path()?, backup_path).map_err(|_| Error::BadDir)?;
+
+    Ok(())
+}
@@ -1,3 +1,6 @@
+pub mod migrations;
 pub mod objects;
+pub mod profiles;
 pub mod queries;
+pub mod snapshots;
 pub mod utils;
@@ -4,7 +4,16 @@ pub struct ConfigItem {
     pub value: String,
 }
 
-#[derive(sqlx::Type)]
+#[derive(sqlx::Type, Clone, Copy)]
 pub enum ConfigParams {
     AccessCheck,
+    KdfSalt,
+    SchemaVersion,
+    AutoBackup,
+    BackupKeepLast,
+    BackupKeepDaily,
+    GeneratorProfiles,
+    PlacePolicies,
+    MinPasswordScore,
+    RefuseWeakPasswords,
 }
@@ -0,0 +1,176 @@
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::consts::DEFAULT_MIN_PASSWORD_SCORE;
+use crate::database::objects::{ConfigItem, ConfigParams};
+use crate::database::queries::DatabaseInterface;
+use crate::errors::Error;
+use crate::json;
+use crate::password::{Generator, GeneratorArgs};
+
+/// Named sets of `generate` options, and the places whose passwords have to be generated with
+/// one of them.
+pub struct GeneratorProfiles {
+    pub profiles: BTreeMap<String, GeneratorArgs>,
+    /// Profile name of each place that has a policy.
+    pub policies: BTreeMap<String, String>,
+}
+
+impl GeneratorProfiles {
+    pub async fn load(conn: &mut DatabaseInterface) -> Result<Self, Error> {
+        Ok(Self {
+            profiles: read_json_setting(conn, ConfigParams::GeneratorProfiles).await?,
+            policies: read_json_setting(conn, ConfigParams::PlacePolicies).await?,
+        })
+    }
+
+    pub async fn save(&self, conn: &mut DatabaseInterface) -> Result<(), Error> {
+        let settings = [
+            (
+                ConfigParams::GeneratorProfiles,
+                json::to_string(&self.profiles)?,
+            ),
+            (
+                ConfigParams::PlacePolicies,
+                json::to_string(&self.policies)?,
+            ),
+        ];
+
+        for (name, value) in settings {
+            conn.update_setting(ConfigItem { name, value }).await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&GeneratorArgs, Error> {
+        self.profiles
+            .get(name)
+            .ok_or(Error::NoProfile(name.to_string()))
+    }
+
+    /// Generator of the profile attached to `place`, which `generate --save` and `rotate` make
+    /// its passwords with.
+    pub fn policy(&self, place: &str) -> Result<Option<Generator>, Error> {
+        self.policies
+            .get(place)
+            .map(|name| self.get(name)?.generator())
+            .transpose()
+    }
+
+    /// Generator for a password of `place`: the `profile` asked for, else the policy of the place,
+    /// else `options`. Asking for another profile than the policy, or for options other than the
+    /// defaults on a place with a policy, is refused.
+    pub fn pick(
+        &self,
+        place: Option<&str>,
+        profile: Option<&str>,
+        options: &GeneratorArgs,
+    ) -> Result<Generator, Error> {
+        if let Some((place, policy)) =
+            place.and_then(|place| Some((place, self.policies.get(place)?)))
+        {
+            let overridden = match profile {
+                Some(profile) => profile != policy,
+                None => *options != GeneratorArgs::default(),
+            };
+
+            if overridden {
+                return Err(Error::PolicyConflict(place.to_string(), policy.to_string()));
+            }
+        }
+
+        if let Some(profile) = profile {
+            return self.get(profile)?.generator();
+        }
+
+        match place.map(|place| self.policy(place)).transpose()?.flatten() {
+            Some(generator) => Ok(generator),
+            None => options.generator(),
+        }
+    }
+
+    /// Gives `to` the policy of `from` after an entry was renamed, unless it has one already, and
+    /// drops the policy of `from` if no entry is left there.
+    pub async fn follow_rename(
+        conn: &mut DatabaseInterface,
+        from: &str,
+        to: &str,
+    ) -> Result<(), Error> {
+        let mut profiles = Self::load(conn).await?;
+
+        if let Some(profile) = profiles.policies.get(from).cloned() {
+            profiles.policies.entry(to.to_string()).or_insert(profile);
+            profiles.save(conn).await?;
+        }
+
+        Self::forget_missing_places(conn).await
+    }
+
+    /// Drops the policies of places no entry, trashed or not, is left at.
+    pub async fn forget_missing_places(conn: &mut DatabaseInterface) -> Result<(), Error> {
+        let places = conn
+            .get_all_passwords()
+            .await?
+            .into_iter()
+            .map(|password| password.place)
+            .collect::<HashSet<_>>();
+        let mut profiles = Self::load(conn).await?;
+        let count = profiles.policies.len();
+
+        profiles.policies.retain(|place, _| places.contains(place));
+
+        if profiles.policies.len() == count {
+            return Ok(());
+        }
+
+        profiles.save(conn).await
+    }
+}
+
+/// How strong saved passwords have to be, and whether weaker ones are refused or only warned
+/// about.
+pub struct StrengthPolicy {
+    /// Least score, from 0 to 4.
+    pub min_score: u8,
+    pub refuse: bool,
+}
+
+impl StrengthPolicy {
+    /// Reads the policy from the config, falling back to the defaults for unset values.
+    pub async fn load(conn: &mut DatabaseInterface) -> Result<Self, Error> {
+        let min_score = match conn.find_setting(ConfigParams::MinPasswordScore).await? {
+            Some(value) => value.parse().map_err(|_| Error::ParsingError)?,
+            None => DEFAULT_MIN_PASSWORD_SCORE,
+        };
+        let refuse = match conn.find_setting(ConfigParams::RefuseWeakPasswords).await? {
+            Some(value) => value.parse().map_err(|_| Error::ParsingError)?,
+            None => false,
+        };
+
+        Ok(Self { min_score, refuse })
+    }
+
+    pub async fn save(&self, conn: &mut DatabaseInterface) -> Result<(), Error> {
+        let settings = [
+            (ConfigParams::MinPasswordScore, self.min_score.to_string()),
+            (ConfigParams::RefuseWeakPasswords, self.refuse.to_string()),
+        ];
+
+        for (name, value) in settings {
+            conn.update_setting(ConfigItem { name, value }).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn read_json_setting<T: DeserializeOwned + Default>(
+    conn: &mut DatabaseInterface,
+    setting: ConfigParams,
+) -> Result<T, Error> {
+    match conn.find_setting(setting).await? {
+        Some(value) => serde_json::from_str(&value).map_err(|_| Error::ParsingError),
+        None => Ok(T::default()),
+    }
+}
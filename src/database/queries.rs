@@ -1,9 +1,9 @@
-use sqlx::{self, SqliteConnection};
+use sqlx::{self, Connection, SqliteConnection};
 
 use crate::{
     database::objects::{ConfigItem, ConfigParams},
     errors::Error,
-    password::Password,
+    password::{CustomField, HistoryEntry, Password},
 };
 
 use super::utils::get_sqlite_connection;
@@ -23,12 +23,27 @@ impl DatabaseInterface {
         Self { connection }
     }
 
+    pub async fn close(self) -> Result<(), Error> {
+        self.connection.close().await.map_err(Error::DatabaseError)
+    }
+
     pub async fn get_setting(&mut self, setting: ConfigParams) -> Result<ConfigItem, Error> {
         sqlx::query_as::<_, ConfigItem>("SELECT * FROM config WHERE name = ?;")
             .bind(setting)
             .fetch_one(&mut self.connection)
             .await
-            .map_err(|err| Error::DatabaseError(err))
+            .map_err(Error::DatabaseError)
+    }
+
+    /// Value of the setting, or `None` when it was never set.
+    pub async fn find_setting(&mut self, setting: ConfigParams) -> Result<Option<String>, Error> {
+        let item = sqlx::query_as::<_, ConfigItem>("SELECT * FROM config WHERE name = ?;")
+            .bind(setting)
+            .fetch_optional(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(item.map(|item| item.value))
     }
 
     pub async fn set_setting(&mut self, setting: ConfigItem) -> Result<(), Error> {
@@ -37,7 +52,54 @@ impl DatabaseInterface {
             .bind(setting.value)
             .execute(&mut self.connection)
             .await
-            .map_err(|err| Error::DatabaseError(err))?;
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn update_setting(&mut self, setting: ConfigItem) -> Result<(), Error> {
+        sqlx::query("INSERT OR REPLACE INTO config (name, value) VALUES (?, ?);")
+            .bind(setting.name)
+            .bind(setting.value)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn execute(&mut self, statement: &str) -> Result<(), Error> {
+        sqlx::query(statement)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn begin(&mut self) -> Result<(), Error> {
+        sqlx::query("BEGIN IMMEDIATE;")
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn commit(&mut self) -> Result<(), Error> {
+        sqlx::query("COMMIT;")
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn rollback(&mut self) -> Result<(), Error> {
+        sqlx::query("ROLLBACK;")
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
 
         Ok(())
     }
@@ -46,7 +108,7 @@ impl DatabaseInterface {
         sqlx::query("CREATE TABLE passwords (password TEXT, username TEXT, place TEXT PRIMARY KEY, encrypted NUMBER);")
             .execute(&mut self.connection)
             .await
-            .map_err(|err| Error::DatabaseError(err))?;
+            .map_err(Error::DatabaseError)?;
 
         Ok(())
     }
@@ -55,49 +117,126 @@ impl DatabaseInterface {
         sqlx::query("CREATE TABLE config (name TEXT PRIMARY KEY, value TEXT);")
             .execute(&mut self.connection)
             .await
-            .map_err(|err| Error::DatabaseError(err))?;
+            .map_err(Error::DatabaseError)?;
 
         Ok(())
     }
 
     pub async fn get_password(&mut self, place: &str) -> Result<Vec<Password>, Error> {
-        sqlx::query_as::<_, Password>("SELECT * FROM passwords WHERE place = ?;")
-            .bind(place)
-            .fetch_all(&mut self.connection)
-            .await
-            .map_err(|err| Error::DatabaseError(err))
+        let passwords = sqlx::query_as::<_, Password>(
+            "SELECT * FROM passwords WHERE place = ? AND deleted_at IS NULL;",
+        )
+        .bind(place)
+        .fetch_all(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)?;
+
+        self.with_details(passwords).await
     }
 
-    pub async fn delete_password(&mut self, place: &str) -> Result<(), Error> {
-        sqlx::query("DELETE FROM passwords WHERE place = ?;")
-            .bind(place)
+    pub async fn get_trashed_password(&mut self, place: &str) -> Result<Vec<Password>, Error> {
+        let passwords = sqlx::query_as::<_, Password>(
+            "SELECT * FROM passwords WHERE place = ? AND deleted_at IS NOT NULL;",
+        )
+        .bind(place)
+        .fetch_all(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)?;
+
+        self.with_details(passwords).await
+    }
+
+    pub async fn delete_password(&mut self, id: i64) -> Result<(), Error> {
+        self.set_tags(id, &[]).await?;
+        self.set_fields(id, &[]).await?;
+
+        sqlx::query("DELETE FROM password_history WHERE password_id = ?;")
+            .bind(id)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        sqlx::query("DELETE FROM passwords WHERE id = ?;")
+            .bind(id)
             .execute(&mut self.connection)
             .await
-            .map_err(|err| Error::DatabaseError(err))?;
+            .map_err(Error::DatabaseError)?;
 
         Ok(())
     }
 
     pub async fn insert_password(&mut self, password: &Password) -> Result<(), Error> {
-        sqlx::query(
-            "INSERT INTO passwords (place, password, username, encrypted) VALUES (?, ?, ?, ?);",
+        let id = sqlx::query(
+            "INSERT INTO passwords
+                (place, password, username, cipher, kind, folder, notes, totp, created_at,
+                updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, unixepoch(), unixepoch());",
         )
         .bind(&password.place)
         .bind(&password.password)
         .bind(&password.username)
-        .bind(password.encrypted)
+        .bind(password.cipher)
+        .bind(password.kind)
+        .bind(&password.folder)
+        .bind(&password.notes)
+        .bind(&password.totp)
         .execute(&mut self.connection)
         .await
-        .map_err(|err| Error::DatabaseError(err))?;
+        .map_err(Error::DatabaseError)?
+        .last_insert_rowid();
 
-        Ok(())
+        self.set_tags(id, &password.tags).await?;
+        self.set_fields(id, &password.fields).await
     }
 
+    /// Every saved password, including the ones in the trash.
     pub async fn get_all_passwords(&mut self) -> Result<Vec<Password>, Error> {
-        sqlx::query_as("SELECT * FROM passwords;")
+        let passwords = sqlx::query_as("SELECT * FROM passwords;")
             .fetch_all(&mut self.connection)
             .await
-            .map_err(|err| Error::DatabaseError(err))
+            .map_err(Error::DatabaseError)?;
+
+        self.with_details(passwords).await
+    }
+
+    pub async fn get_active_passwords(&mut self) -> Result<Vec<Password>, Error> {
+        let passwords = sqlx::query_as("SELECT * FROM passwords WHERE deleted_at IS NULL;")
+            .fetch_all(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        self.with_details(passwords).await
+    }
+
+    pub async fn get_trashed_passwords(&mut self) -> Result<Vec<Password>, Error> {
+        let passwords = sqlx::query_as(
+            "SELECT * FROM passwords WHERE deleted_at IS NOT NULL ORDER BY deleted_at;",
+        )
+        .fetch_all(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)?;
+
+        self.with_details(passwords).await
+    }
+
+    pub async fn trash_password(&mut self, id: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE passwords SET deleted_at = unixepoch() WHERE id = ?;")
+            .bind(id)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+
+    pub async fn untrash_password(&mut self, id: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE passwords SET deleted_at = NULL WHERE id = ?;")
+            .bind(id)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
     }
 
     pub async fn list_tables(&mut self) -> Result<Vec<String>, Error> {
@@ -105,7 +244,7 @@ impl DatabaseInterface {
             sqlx::query_as("SELECT name FROM sqlite_master WHERE type='table';")
                 .fetch_all(&mut self.connection)
                 .await
-                .map_err(|err| Error::DatabaseError(err))?;
+                .map_err(Error::DatabaseError)?;
 
         Ok(row.into_iter().map(|v| v.0).collect::<Vec<String>>())
     }
@@ -115,22 +254,200 @@ impl DatabaseInterface {
             .bind(setting)
             .fetch_all(&mut self.connection)
             .await
-            .map_err(|err| Error::DatabaseError(err))?;
+            .map_err(Error::DatabaseError)?;
 
-        Ok(result.len() >= 1)
+        Ok(!result.is_empty())
     }
 
     pub async fn update_password(&mut self, password: &Password) -> Result<(), Error> {
         sqlx::query(
-            "UPDATE passwords SET password = ?, username = ?, encrypted = ? WHERE place = ?;",
+            "UPDATE passwords SET place = ?, password = ?, username = ?, cipher = ?, kind = ?,
+                folder = ?, notes = ?, totp = ?, updated_at = unixepoch() WHERE id = ?;",
         )
+        .bind(&password.place)
         .bind(&password.password)
         .bind(&password.username)
-        .bind(password.encrypted)
-        .bind(&password.place)
+        .bind(password.cipher)
+        .bind(password.kind)
+        .bind(&password.folder)
+        .bind(&password.notes)
+        .bind(&password.totp)
+        .bind(password.id)
+        .execute(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)?;
+
+        self.set_tags(password.id, &password.tags).await?;
+        self.set_fields(password.id, &password.fields).await
+    }
+
+    /// Stores a password sealed under a different key or cipher without marking it as changed.
+    pub async fn reencrypt_password(&mut self, password: &Password) -> Result<(), Error> {
+        sqlx::query(
+            "UPDATE passwords SET password = ?, notes = ?, totp = ?, cipher = ? WHERE id = ?;",
+        )
+        .bind(&password.password)
+        .bind(&password.notes)
+        .bind(&password.totp)
+        .bind(password.cipher)
+        .bind(password.id)
         .execute(&mut self.connection)
         .await
-        .map_err(|err| Error::DatabaseError(err))?;
+        .map_err(Error::DatabaseError)?;
+
+        self.set_fields(password.id, &password.fields).await
+    }
+
+    pub async fn touch_password(&mut self, id: i64) -> Result<(), Error> {
+        sqlx::query("UPDATE passwords SET last_used_at = unixepoch() WHERE id = ?;")
+            .bind(id)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+    pub async fn set_tags(&mut self, password_id: i64, tags: &[String]) -> Result<(), Error> {
+        sqlx::query("DELETE FROM password_tags WHERE password_id = ?;")
+            .bind(password_id)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        for tag in tags {
+            sqlx::query("INSERT OR IGNORE INTO password_tags (password_id, tag) VALUES (?, ?);")
+                .bind(password_id)
+                .bind(tag)
+                .execute(&mut self.connection)
+                .await
+                .map_err(Error::DatabaseError)?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_fields(
+        &mut self,
+        password_id: i64,
+        fields: &[CustomField],
+    ) -> Result<(), Error> {
+        sqlx::query("DELETE FROM password_fields WHERE password_id = ?;")
+            .bind(password_id)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+
+        for field in fields {
+            sqlx::query(
+                "INSERT OR REPLACE INTO password_fields (password_id, name, value, concealed)
+                    VALUES (?, ?, ?, ?);",
+            )
+            .bind(password_id)
+            .bind(&field.name)
+            .bind(&field.value)
+            .bind(field.concealed)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Attaches the tags and custom fields stored in their own tables.
+    async fn with_details(&mut self, mut passwords: Vec<Password>) -> Result<Vec<Password>, Error> {
+        let tags: Vec<(i64, String)> =
+            sqlx::query_as("SELECT password_id, tag FROM password_tags ORDER BY tag;")
+                .fetch_all(&mut self.connection)
+                .await
+                .map_err(Error::DatabaseError)?;
+        let fields: Vec<(i64, CustomField)> = sqlx::query_as::<_, (i64, String, String, bool)>(
+            "SELECT password_id, name, value, concealed FROM password_fields ORDER BY name;",
+        )
+        .fetch_all(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)?
+        .into_iter()
+        .map(|(password_id, name, value, concealed)| {
+            (
+                password_id,
+                CustomField {
+                    name,
+                    value,
+                    concealed,
+                },
+            )
+        })
+        .collect();
+
+        for password in passwords.iter_mut() {
+            password.tags = tags
+                .iter()
+                .filter(|(password_id, _)| *password_id == password.id)
+                .map(|(_, tag)| tag.clone())
+                .collect();
+            password.fields = fields
+                .iter()
+                .filter(|(password_id, _)| *password_id == password.id)
+                .map(|(_, field)| field.clone())
+                .collect();
+        }
+
+        Ok(passwords)
+    }
+
+    pub async fn insert_history(&mut self, previous: &Password) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO password_history (password_id, password, cipher, replaced_at)
+                VALUES (?, ?, ?, unixepoch());",
+        )
+        .bind(previous.id)
+        .bind(&previous.password)
+        .bind(previous.cipher)
+        .execute(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)?;
+
+        Ok(())
+    }
+
+    /// Previous values of a password, oldest first.
+    pub async fn get_history(&mut self, password_id: i64) -> Result<Vec<HistoryEntry>, Error> {
+        sqlx::query_as::<_, HistoryEntry>(
+            "SELECT h.id AS history_id, h.replaced_at, p.id, h.password, p.username, p.place,
+                h.cipher, p.kind, p.created_at, p.updated_at, p.last_used_at, p.deleted_at, p.folder,
+                '' AS notes,
+                '' AS totp
+                FROM password_history h JOIN passwords p ON p.id = h.password_id
+                WHERE h.password_id = ? ORDER BY h.replaced_at, h.id;",
+        )
+        .bind(password_id)
+        .fetch_all(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)
+    }
+
+    pub async fn get_all_history(&mut self) -> Result<Vec<HistoryEntry>, Error> {
+        sqlx::query_as::<_, HistoryEntry>(
+            "SELECT h.id AS history_id, h.replaced_at, p.id, h.password, p.username, p.place,
+                h.cipher, p.kind, p.created_at, p.updated_at, p.last_used_at, p.deleted_at, p.folder,
+                '' AS notes,
+                '' AS totp
+                FROM password_history h JOIN passwords p ON p.id = h.password_id;",
+        )
+        .fetch_all(&mut self.connection)
+        .await
+        .map_err(Error::DatabaseError)
+    }
+
+    pub async fn reencrypt_history(&mut self, entry: &HistoryEntry) -> Result<(), Error> {
+        sqlx::query("UPDATE password_history SET password = ?, cipher = ? WHERE id = ?;")
+            .bind(&entry.password.password)
+            .bind(entry.password.cipher)
+            .bind(entry.history_id)
+            .execute(&mut self.connection)
+            .await
+            .map_err(Error::DatabaseError)?;
 
         Ok(())
     }
@@ -0,0 +1,200 @@
+use chrono::{Local, NaiveDateTime};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::consts::{SNAPSHOT_KEEP_DAILY, SNAPSHOT_KEEP_LAST};
+use crate::database::objects::{ConfigItem, ConfigParams};
+use crate::database::queries::DatabaseInterface;
+use crate::database::utils::get_save_dir_path;
+use crate::errors::Error;
+
+const SNAPSHOT_ID_FORMAT: &str = "%Y%m%d-%H%M%S";
+const SNAPSHOT_PREFIX: &str = "data-";
+const SNAPSHOT_EXTENSION: &str = ".sqlite";
+
+/// Copy of the database file taken by the automatic backups. Its id is the local time it was
+/// taken at, followed by `-<sequence>` when an earlier snapshot was taken in the same second.
+pub struct Snapshot {
+    pub id: String,
+    pub path: PathBuf,
+    pub taken_at: NaiveDateTime,
+    /// Order of the snapshot among the ones taken in the same second, starting at 0.
+    pub sequence: u32,
+}
+
+/// When automatic backups run and how many of them are kept.
+pub struct SnapshotPolicy {
+    pub enabled: bool,
+    /// Number of most recent snapshots always kept.
+    pub keep_last: usize,
+    /// Number of days, today included, for which the last snapshot of the day is kept.
+    pub keep_daily: usize,
+}
+
+impl SnapshotPolicy {
+    /// Reads the policy from the config, falling back to the defaults for unset values.
+    pub async fn load(conn: &mut DatabaseInterface) -> Result<Self, Error> {
+        let enabled = match conn.find_setting(ConfigParams::AutoBackup).await? {
+            Some(value) => value.parse().map_err(|_| Error::ParsingError)?,
+            None => true,
+        };
+        let keep_last = match conn.find_setting(ConfigParams::BackupKeepLast).await? {
+            Some(value) => value.parse().map_err(|_| Error::ParsingError)?,
+            None => SNAPSHOT_KEEP_LAST,
+        };
+        let keep_daily = match conn.find_setting(ConfigParams::BackupKeepDaily).await? {
+            Some(value) => value.parse().map_err(|_| Error::ParsingError)?,
+            None => SNAPSHOT_KEEP_DAILY,
+        };
+
+        Ok(Self {
+            enabled,
+            keep_last,
+            keep_daily,
+        })
+    }
+
+    pub async fn save(&self, conn: &mut DatabaseInterface) -> Result<(), Error> {
+        let settings = [
+            (ConfigParams::AutoBackup, self.enabled.to_string()),
+            (ConfigParams::BackupKeepLast, self.keep_last.to_string()),
+            (ConfigParams::BackupKeepDaily, self.keep_daily.to_string()),
+        ];
+
+        for (name, value) in settings {
+            conn.update_setting(ConfigItem { name, value }).await?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn get_snapshot_dir_path() -> Result<PathBuf, Error> {
+    Ok(get_save_dir_path()?.join("backups/"))
+}
+
+/// Every snapshot in the backups directory, newest first.
+pub fn list_snapshots() -> Result<Vec<Snapshot>, Error> {
+    list_snapshots_in(&get_snapshot_dir_path()?)
+}
+
+/// Every snapshot in `dir`, newest first. Unrelated files are ignored.
+pub fn list_snapshots_in(dir: &Path) -> Result<Vec<Snapshot>, Error> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = fs::read_dir(dir)
+        .map_err(|_| Error::BadDir)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let id = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix(SNAPSHOT_PREFIX)?
+                .strip_suffix(SNAPSHOT_EXTENSION)?
+                .to_string();
+            let (taken_at, rest) =
+                NaiveDateTime::parse_and_remainder(&id, SNAPSHOT_ID_FORMAT).ok()?;
+            let sequence = match rest {
+                "" => 0,
+                _ => rest.strip_prefix('-')?.parse().ok()?,
+            };
+
+            Some(Snapshot {
+                id,
+                path,
+                taken_at,
+                sequence,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse((snapshot.taken_at, snapshot.sequence)));
+
+    Ok(snapshots)
+}
+
+pub fn find_snapshot(id: &str) -> Result<Snapshot, Error> {
+    list_snapshots()?
+        .into_iter()
+        .find(|snapshot| snapshot.id == id)
+        .ok_or(Error::NoSnapshot(id.to_string()))
+}
+
+/// Copies the database into a new snapshot. `VACUUM INTO` gives a consistent copy even though
+/// the connection is open.
+pub async fn take_snapshot(conn: &mut DatabaseInterface) -> Result<Snapshot, Error> {
+    let dir = get_snapshot_dir_path()?;
+    let taken_at = Local::now().naive_local();
+    let time = taken_at.format(SNAPSHOT_ID_FORMAT).to_string();
+
+    fs::create_dir_all(&dir).map_err(|_| Error::BadDir)?;
+
+    // Snapshots taken in the same second get increasing sequence numbers instead of replacing
+    // each other.
+    let mut sequence = 0;
+    let (id, path) = loop {
+        let id = match sequence {
+            0 => time.clone(),
+            _ => format!("{}-{}", time, sequence),
+        };
+        let path = dir.join(format!("{}{}{}", SNAPSHOT_PREFIX, id, SNAPSHOT_EXTENSION));
+
+        if !path.exists() {
+            break (id, path);
+        }
+
+        sequence += 1;
+    };
+
+    let target = path.to_str().ok_or(Error::BadDir)?.replace('\'', "''");
+    conn.execute(&format!("VACUUM INTO '{}';", target)).await?;
+
+    Ok(Snapshot {
+        id,
+        path,
+        taken_at,
+        sequence,
+    })
+}
+
+/// Takes a snapshot if automatic backups are enabled, then deletes the ones the policy no longer
+/// keeps.
+pub async fn auto_snapshot(conn: &mut DatabaseInterface) -> Result<(), Error> {
+    let policy = SnapshotPolicy::load(conn).await?;
+
+    if !policy.enabled {
+        return Ok(());
+    }
+
+    take_snapshot(conn).await?;
+    prune_snapshots(
+        &get_snapshot_dir_path()?,
+        &policy,
+        Local::now().naive_local(),
+    )
+}
+
+/// Deletes the snapshots in `dir` that `policy` no longer keeps, counting days back from `now`.
+pub fn prune_snapshots(
+    dir: &Path,
+    policy: &SnapshotPolicy,
+    now: NaiveDateTime,
+) -> Result<(), Error> {
+    let today = now.date();
+    let mut kept_days = HashSet::new();
+
+    for (index, snapshot) in list_snapshots_in(dir)?.into_iter().enumerate() {
+        let day = snapshot.taken_at.date();
+        let age_in_days = (today - day).num_days();
+        let keep_for_day = age_in_days < policy.keep_daily as i64 && kept_days.insert(day);
+
+        if index >= policy.keep_last && !keep_for_day {
+            fs::remove_file(&snapshot.path).map_err(|_| Error::BadDir)?;
+        }
+    }
+
+    Ok(())
+}
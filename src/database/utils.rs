@@ -1,7 +1,10 @@
 use crate::consts::HASH_COST;
+use crate::database::migrations::{migrate, KNOWN_TABLES};
 use crate::database::objects::{ConfigItem, ConfigParams};
 use crate::database::queries::DatabaseInterface;
 use crate::errors::Error;
+use crate::utils::generate_salt;
+use base64::prelude::{Engine, BASE64_STANDARD};
 use bcrypt::hash;
 use sqlx::{Connection, SqliteConnection};
 use std::fs;
@@ -24,14 +27,14 @@ pub fn get_save_dir_path() -> Result<PathBuf, Error> {
     Ok(get_home_path()?.join(".password-manager/"))
 }
 
-fn get_save_file_path() -> Result<PathBuf, Error> {
+pub fn get_save_file_path() -> Result<PathBuf, Error> {
     Ok(get_save_dir_path()?.join("data.sqlite"))
 }
 
 pub async fn get_sqlite_connection() -> Result<SqliteConnection, Error> {
     SqliteConnection::connect(&(get_save_file_path()?.display().to_string() + "?mode=rwc"))
         .await
-        .map_err(|err| Error::DatabaseError(err))
+        .map_err(Error::DatabaseError)
 }
 
 pub async fn create_new_save_file(new_key: &str) -> Result<(), Error> {
@@ -43,6 +46,7 @@ pub async fn create_new_save_file(new_key: &str) -> Result<(), Error> {
 
         conn.create_config_table().await?;
         conn.create_password_table().await?;
+        migrate(&mut conn, false).await?;
 
         save_new_key(new_key, &mut conn).await?;
 
@@ -53,22 +57,65 @@ pub async fn create_new_save_file(new_key: &str) -> Result<(), Error> {
 }
 
 pub async fn save_new_key(key: &str, conn: &mut DatabaseInterface) -> Result<(), Error> {
-    let hashed_key = hash(key, HASH_COST).map_err(|err| Error::HashError(err))?;
+    let hashed_key = hash(key, HASH_COST).map_err(Error::HashError)?;
     let setting = ConfigItem {
         name: ConfigParams::AccessCheck,
         value: hashed_key,
     };
 
-    conn.set_setting(setting).await?;
+    conn.set_setting(setting).await
+}
+
+pub async fn replace_key(
+    key: &str,
+    salt: &[u8],
+    conn: &mut DatabaseInterface,
+) -> Result<(), Error> {
+    let hashed_key = hash(key, HASH_COST).map_err(Error::HashError)?;
+    let key_setting = ConfigItem {
+        name: ConfigParams::AccessCheck,
+        value: hashed_key,
+    };
+    let salt_setting = ConfigItem {
+        name: ConfigParams::KdfSalt,
+        value: BASE64_STANDARD.encode(salt),
+    };
+
+    conn.update_setting(key_setting).await?;
+    conn.update_setting(salt_setting).await?;
 
     Ok(())
 }
 
+/// Stores a new key derivation salt unless the vault already has one.
+pub async fn create_kdf_salt(conn: &mut DatabaseInterface) -> Result<(), Error> {
+    if conn.has_setting(ConfigParams::KdfSalt).await? {
+        return Ok(());
+    }
+
+    let setting = ConfigItem {
+        name: ConfigParams::KdfSalt,
+        value: BASE64_STANDARD.encode(generate_salt()),
+    };
+
+    conn.set_setting(setting).await
+}
+
+/// Returns the vault's key derivation salt, which the migrations create.
+pub async fn get_kdf_salt(conn: &mut DatabaseInterface) -> Result<Vec<u8>, Error> {
+    let setting = conn.get_setting(ConfigParams::KdfSalt).await?;
+
+    BASE64_STANDARD
+        .decode(setting.value)
+        .map_err(|_| Error::Other("invalid key derivation salt".to_string()))
+}
+
 pub async fn create_save_file() -> Result<(), Error> {
     let mut conn = DatabaseInterface::new().await?;
 
     conn.create_config_table().await?;
     conn.create_password_table().await?;
+    migrate(&mut conn, false).await?;
 
     Ok(())
 }
@@ -77,7 +124,7 @@ pub async fn has_correct_tables(conn: &mut DatabaseInterface) -> Result<bool, Er
     let tables = conn.list_tables().await?;
 
     for table in tables {
-        if !(&table == "passwords" || &table == "config") {
+        if !KNOWN_TABLES.contains(&table.as_str()) {
             return Err(Error::UnexpectedTable(table));
         }
     }
@@ -94,6 +141,8 @@ pub async fn get_validated_conn() -> Result<DatabaseInterface, Error> {
         let mut conn = DatabaseInterface::new().await?;
 
         if has_correct_tables(&mut conn).await? && has_key(&mut conn).await? {
+            migrate(&mut conn, true).await?;
+
             return Ok(conn);
         }
     }
@@ -0,0 +1,283 @@
+use std::fmt;
+
+use crate::consts::communications::{DIFF_ENCRYPTION, DIFF_PASSWORD, DIFF_USERNAME};
+use crate::consts::{
+    CSV_ENCRYPTED, CSV_FOLDER, CSV_KDF_SALT, CSV_KIND, CSV_NOTES, CSV_PASSWORD, CSV_PLACE,
+    CSV_TAGS, CSV_TOTP, CSV_USERNAME, TAG_SEPARATOR,
+};
+use crate::csv;
+use crate::errors::Error;
+use crate::password::{field_headers, CustomField, EntryKind, Password};
+use crate::utils::find_clomun_index;
+
+/// Passwords as `dump-database` prints them, sealed as they are stored. `kdf_salt` is the
+/// vault's salt, written on every row so `load-dump` can tell which vault sealed them.
+pub fn write(passwords: &[Password], kdf_salt: &str) -> String {
+    let field_headers = field_headers(passwords);
+    let mut headers = vec![
+        CSV_PLACE,
+        CSV_USERNAME,
+        CSV_PASSWORD,
+        CSV_ENCRYPTED,
+        CSV_KIND,
+        CSV_FOLDER,
+        CSV_TAGS,
+        CSV_NOTES,
+        CSV_TOTP,
+        CSV_KDF_SALT,
+    ];
+    headers.extend(field_headers.iter().map(String::as_str));
+
+    let mut contents = csv::write_record(&headers);
+
+    for password in passwords {
+        contents.push_str(&password.dump(kdf_salt, &field_headers));
+    }
+
+    contents
+}
+
+/// Reads a dump written by `write`, along with the salt each row was sealed with. The user is
+/// asked for the name of any required column the header lacks.
+pub fn read(contents: &str) -> Result<Vec<(Password, Option<String>)>, Error> {
+    let mut records = csv::parse(contents)?.into_iter();
+    let header = records.next().ok_or(Error::BadDump)?;
+    let header_parts = header.fields.iter().map(String::as_str);
+
+    if header_parts.clone().count() < 4 {
+        return Err(Error::BadHeaders);
+    }
+
+    let place_index = find_clomun_index(
+        CSV_PLACE,
+        "Enter `place` column name:",
+        header_parts.clone(),
+    )?;
+    let username_index = find_clomun_index(
+        CSV_USERNAME,
+        "Enter `username` column name:",
+        header_parts.clone(),
+    )?;
+    let password_index = find_clomun_index(
+        CSV_PASSWORD,
+        "Enter `password` column name:",
+        header_parts.clone(),
+    )?;
+    let encrypted_index = find_clomun_index(
+        CSV_ENCRYPTED,
+        "Enter `encrypted` column name:",
+        header_parts.clone(),
+    )?;
+    let kind_index = header_parts.clone().position(|header| header == CSV_KIND);
+    let folder_index = header_parts.clone().position(|header| header == CSV_FOLDER);
+    let tags_index = header_parts.clone().position(|header| header == CSV_TAGS);
+    let notes_index = header_parts.clone().position(|header| header == CSV_NOTES);
+    let totp_index = header_parts.clone().position(|header| header == CSV_TOTP);
+    let kdf_salt_index = header_parts
+        .clone()
+        .position(|header| header == CSV_KDF_SALT);
+    let field_columns = header_parts
+        .clone()
+        .enumerate()
+        .filter_map(|(index, header)| CustomField::from_header(header).map(|field| (index, field)))
+        .collect::<Vec<_>>();
+
+    let mut passwords = Vec::new();
+
+    for record in records {
+        let line = record.line;
+        let parts = record.fields;
+
+        let place = parts
+            .get(place_index)
+            .ok_or(Error::MissingField(CSV_PLACE, line))?
+            .to_owned();
+        let username = parts
+            .get(username_index)
+            .ok_or(Error::MissingField(CSV_USERNAME, line))?
+            .to_owned();
+        let password = parts
+            .get(password_index)
+            .ok_or(Error::MissingField(CSV_PASSWORD, line))?
+            .to_owned();
+        let encrypted = parts
+            .get(encrypted_index)
+            .ok_or(Error::MissingField(CSV_ENCRYPTED, line))?;
+
+        let mut new_password = Password {
+            id: 0,
+            place,
+            username,
+            password,
+            cipher: encrypted
+                .parse::<i32>()
+                .map_err(|_| Error::ParsingError)?
+                .try_into()?,
+            kind: match kind_index.and_then(|index| parts.get(index)) {
+                Some(kind) => kind
+                    .parse::<i32>()
+                    .map_err(|_| Error::ParsingError)?
+                    .try_into()?,
+                None => EntryKind::Login,
+            },
+            created_at: 0,
+            updated_at: 0,
+            last_used_at: None,
+            deleted_at: None,
+            folder: String::new(),
+            tags: Vec::new(),
+            notes: notes_index
+                .and_then(|index| parts.get(index))
+                .cloned()
+                .unwrap_or_default(),
+            fields: Vec::new(),
+            totp: totp_index
+                .and_then(|index| parts.get(index))
+                .cloned()
+                .unwrap_or_default(),
+        };
+
+        for (index, field) in field_columns.iter() {
+            if let Some(value) = parts.get(*index).filter(|value| !value.is_empty()) {
+                new_password.set_field(CustomField {
+                    value: value.to_owned(),
+                    ..field.clone()
+                });
+            }
+        }
+
+        if let Some(folder) = folder_index.and_then(|index| parts.get(index)) {
+            new_password.set_folder(folder);
+        }
+        if let Some(tags) = tags_index.and_then(|index| parts.get(index)) {
+            new_password.set_tags(&tags.split(TAG_SEPARATOR).collect::<Vec<_>>())?;
+        }
+
+        let kdf_salt = kdf_salt_index
+            .and_then(|index| parts.get(index))
+            .filter(|salt| !salt.is_empty())
+            .cloned();
+
+        passwords.push((new_password, kdf_salt));
+    }
+
+    Ok(passwords)
+}
+
+/// What differs between a stored entry and its counterpart in a dump.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Difference {
+    /// The dumped username, when the entries were matched by place alone.
+    Username(String),
+    Encryption,
+    Password,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Username(username) => write!(f, "{} {}", DIFF_USERNAME, username),
+            Difference::Encryption => write!(f, "{}", DIFF_ENCRYPTION),
+            Difference::Password => write!(f, "{}", DIFF_PASSWORD),
+        }
+    }
+}
+
+/// Stored entries and rows of a dump, matched by place and username, then by place alone, so an
+/// entry whose username changed shows up as different rather than on both sides.
+pub struct Matches {
+    /// Stored entries with their row in the dump and the salt it was sealed with.
+    pub pairs: Vec<(Password, Password, Option<String>)>,
+    pub only_in_dump: Vec<Password>,
+    pub only_in_vault: Vec<Password>,
+}
+
+/// How the vault and a dump differ.
+pub struct Diff {
+    pub only_in_dump: Vec<Password>,
+    pub only_in_vault: Vec<Password>,
+    /// Stored entries whose row in the dump differs, with what differs.
+    pub changed: Vec<(Password, Vec<Difference>)>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_dump.is_empty() && self.only_in_vault.is_empty() && self.changed.is_empty()
+    }
+}
+
+pub fn match_entries(
+    mut stored: Vec<Password>,
+    dumped: Vec<(Password, Option<String>)>,
+) -> Matches {
+    let mut take_stored = |matches: &dyn Fn(&Password) -> bool| {
+        stored
+            .iter()
+            .position(matches)
+            .map(|index| stored.remove(index))
+    };
+
+    let mut pairs = Vec::new();
+    let mut unpaired = Vec::new();
+    let mut only_in_dump = Vec::new();
+
+    for (password, kdf_salt) in dumped {
+        match take_stored(&|stored| {
+            stored.place == password.place && stored.username == password.username
+        }) {
+            Some(stored) => pairs.push((stored, password, kdf_salt)),
+            None => unpaired.push((password, kdf_salt)),
+        }
+    }
+    for (password, kdf_salt) in unpaired {
+        match take_stored(&|stored| stored.place == password.place) {
+            Some(stored) => pairs.push((stored, password, kdf_salt)),
+            None => only_in_dump.push(password),
+        }
+    }
+
+    Matches {
+        pairs,
+        only_in_dump,
+        only_in_vault: stored,
+    }
+}
+
+impl Matches {
+    /// Compares every pair. `decrypt` opens the stored entry and its row in the dump, given the
+    /// salt the row was sealed with, once their ciphers have been compared.
+    pub fn compare<F>(self, mut decrypt: F) -> Result<Diff, Error>
+    where
+        F: FnMut(&mut Password, &mut Password, Option<&str>) -> Result<(), Error>,
+    {
+        let mut changed = Vec::new();
+
+        for (mut stored, mut password, kdf_salt) in self.pairs {
+            let mut differences = Vec::new();
+
+            if stored.username != password.username {
+                differences.push(Difference::Username(password.username.clone()));
+            }
+            // Only whether they are encrypted counts: a dump taken before `migrate-crypto` seals
+            // with an older cipher than the vault does.
+            if stored.is_encrypted() != password.is_encrypted() {
+                differences.push(Difference::Encryption);
+            }
+
+            decrypt(&mut stored, &mut password, kdf_salt.as_deref())?;
+
+            if stored.password != password.password {
+                differences.push(Difference::Password);
+            }
+            if !differences.is_empty() {
+                changed.push((stored, differences));
+            }
+        }
+
+        Ok(Diff {
+            only_in_dump: self.only_in_dump,
+            only_in_vault: self.only_in_vault,
+            changed,
+        })
+    }
+}
@@ -14,18 +14,60 @@ pub enum Error {
     ParsingError,
     #[error("Could not find the field {0} (line {1}).")]
     MissingField(&'static str, usize),
+    #[error("Malformed CSV at line {0}, column {1}: {2}.")]
+    MalformedCsv(usize, usize, String),
     #[error("Could not find the header {0}.")]
     NoHeader(String),
     #[error("Invalid headers. There needs to be at least 4 columns.")]
     BadHeaders,
     #[error("Error processing database dump.")]
     BadDump,
+    #[error("Invalid backup file: {0}.")]
+    BadBackup(String),
+    #[error("The backup uses format version {0}, which this program does not support.")]
+    UnsupportedBackup(u32),
+    #[error("Could not decrypt the backup. The passphrase is wrong or the file was modified.")]
+    WrongPassphrase,
+    #[error("Cannot generate the password: {0}.")]
+    ImpossiblePassword(String),
+    #[error("The password scores {0}/4, below the minimum of {1}/4.")]
+    WeakPassword(u8, u8),
+    #[error("Could not find the generation profile {0}.")]
+    NoProfile(String),
+    #[error(
+        "Passwords of {0} follow the profile {1}, which other generation options cannot override."
+    )]
+    PolicyConflict(String, String),
+    #[error("The tag `{0}` cannot contain `{1}`, which separates tags in dumps.")]
+    BadTag(String, &'static str),
+    #[error("Invalid pattern: {0}.")]
+    BadPattern(String),
+    #[error("Invalid wordlist: {0}.")]
+    BadWordlist(String),
+    #[error("Invalid import file: {0}.")]
+    BadImport(String),
+    #[error("The JSON export uses schema version {0}, which this program does not support.")]
+    UnsupportedJson(u32),
+    #[error("GPG failed: {0}")]
+    Gpg(String),
+    #[error("A password store can only hold decrypted passwords.")]
+    EncryptedPassStore,
+    #[error("Give at least one GPG key to encrypt the password store for.")]
+    NoRecipient,
+    #[error("The directory {0} is not empty.")]
+    DirectoryNotEmpty(String),
+    #[error("Could not find the backup {0}.")]
+    NoSnapshot(String),
     #[error("Bad directory.")]
     BadDir,
     #[error("No input.")]
     EmptyInput,
     #[error("Found unexpected table `{0}`.")]
     UnexpectedTable(String),
+    #[error("The database uses schema version {0}, which is newer than this program supports.")]
+    UnsupportedSchema(u32),
+    #[error("Could not apply migration {0} ({1}): {2}")]
+    MigrationError(u32, &'static str, String),
     #[error("Could not find a valid database. Perhaps you forgot to generate it?")]
     MissingDatabase,
     #[error("Save file and key already exists. Cannot regenerate.")]
@@ -38,8 +80,34 @@ pub enum Error {
     VerificationError,
     #[error("Could not decrypt.")]
     BadDecryption(#[from] MagicCryptError),
+    #[error("Could not encrypt.")]
+    EncryptionError,
+    #[error("Could not derive the encryption key: {0}")]
+    KeyDerivationError(String),
+    #[error("Encrypted data is not in a recognised format.")]
+    MalformedCiphertext,
+    #[error(
+        "Encrypted data failed authentication. It was tampered with or sealed with another key."
+    )]
+    TamperedCiphertext,
+    #[error("Unknown cipher version `{0}`.")]
+    UnknownCipher(i32),
+    #[error("Unknown entry type `{0}`.")]
+    UnknownKind(i32),
     #[error("Could not find a password for the place {0}.")]
     NoPassword(String),
+    #[error("A password for {0} is already saved.")]
+    PasswordExists(String),
+    #[error(
+        "A password for {0} is in the trash. Restore it with `trash restore`, or empty the trash first."
+    )]
+    PasswordInTrash(String),
+    #[error("Could not find version {0} in the history of this password.")]
+    NoVersion(usize),
+    #[error("No one-time password is set up for {0}.")]
+    NoTotp(String),
+    #[error("Invalid one-time password secret: {0}.")]
+    BadTotp(String),
     #[error("Error performing database action ({0}).")]
     DatabaseError(#[from] sqlx::Error),
     #[error("Ran into an unexpected issue: {0}")]
@@ -0,0 +1,476 @@
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+use std::collections::HashMap;
+use url::Url;
+
+use crate::consts::{
+    FIELD_URL, IMPORT_UNTITLED, TOTP_DEFAULT_DIGITS, TOTP_DEFAULT_PERIOD, TOTP_URI_PREFIX,
+};
+use crate::csv;
+use crate::errors::Error;
+use crate::json;
+use crate::password::{CustomField, EntryKind, Password};
+use crate::totp::{Totp, TotpAlgorithm};
+
+/// Export formats of other password managers that `import` understands.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImportFormat {
+    /// KeePass 2 (or KeePassXC) unencrypted XML export.
+    KeepassXml,
+    /// Bitwarden unencrypted JSON export.
+    BitwardenJson,
+    /// Chrome, Edge and other Chromium browsers' password CSV.
+    ChromeCsv,
+    /// Firefox password CSV.
+    FirefoxCsv,
+    /// This program's own JSON export.
+    Json,
+    /// Password store directory of `pass`.
+    Pass,
+}
+
+/// Reads an export into passwords, along with the salt of the vault the encrypted ones were
+/// sealed in. Only this program's JSON holds encrypted entries. Values the other formats have no
+/// column for, such as URLs, are kept as custom fields.
+pub fn parse(
+    format: ImportFormat,
+    contents: &str,
+) -> Result<Vec<(Password, Option<String>)>, Error> {
+    let passwords = match format {
+        ImportFormat::KeepassXml => keepass_xml(contents)?,
+        ImportFormat::BitwardenJson => bitwarden_json(contents)?,
+        ImportFormat::ChromeCsv => chrome_csv(contents)?,
+        ImportFormat::FirefoxCsv => firefox_csv(contents)?,
+        ImportFormat::Json => return json::read(contents),
+        ImportFormat::Pass => {
+            return Err(Error::BadImport(
+                "a password store is read from a directory, see `pass::read_store`".to_string(),
+            ))
+        }
+    };
+
+    Ok(passwords
+        .into_iter()
+        .map(|password| (password, None))
+        .collect())
+}
+
+fn keepass_xml(contents: &str) -> Result<Vec<Password>, Error> {
+    let document = Document::parse(contents).map_err(|err| Error::BadImport(err.to_string()))?;
+    let root = document.root_element();
+
+    if !root.has_tag_name("KeePassFile") {
+        return Err(Error::BadImport("not a KeePass XML export".to_string()));
+    }
+
+    let recycle_bin = child(root, "Meta").and_then(|meta| child_text(meta, "RecycleBinUUID"));
+    let top_group = child(root, "Root")
+        .and_then(|root| child(root, "Group"))
+        .ok_or(Error::BadImport("the export has no groups".to_string()))?;
+    let mut passwords = Vec::new();
+
+    // The top group is the database itself, so its entries are not put in a folder.
+    read_keepass_group(top_group, "", recycle_bin, &mut passwords)?;
+
+    Ok(passwords)
+}
+
+fn read_keepass_group(
+    group: Node,
+    folder: &str,
+    recycle_bin: Option<&str>,
+    passwords: &mut Vec<Password>,
+) -> Result<(), Error> {
+    for node in group.children().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "Entry" => passwords.push(read_keepass_entry(node, folder)?),
+            "Group" if child_text(node, "UUID").is_some_and(|uuid| Some(uuid) == recycle_bin) => (),
+            "Group" => {
+                let name = child_text(node, "Name").unwrap_or_default();
+                let folder = if folder.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{}", folder, name)
+                };
+
+                read_keepass_group(node, &folder, recycle_bin, passwords)?;
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the current values of an entry. Its `History` element is ignored.
+fn read_keepass_entry(entry: Node, folder: &str) -> Result<Password, Error> {
+    let mut strings = Vec::new();
+
+    for string in entry.children().filter(|node| node.has_tag_name("String")) {
+        let key = child_text(string, "Key").unwrap_or_default();
+        let value = child(string, "Value");
+        let concealed = value.and_then(|value| value.attribute("ProtectInMemory")) == Some("True");
+        let value = value.and_then(|value| value.text()).unwrap_or_default();
+
+        strings.push((key, value, concealed));
+    }
+
+    let standard = |key: &str| {
+        strings
+            .iter()
+            .find(|(name, _, _)| *name == key)
+            .map(|(_, value, _)| value.to_string())
+            .unwrap_or_default()
+    };
+    let url = standard("URL");
+
+    let mut password = Password::new(
+        standard("UserName"),
+        place_for(&standard("Title"), &url),
+        standard("Password"),
+    );
+
+    password.notes = standard("Notes");
+    password.set_folder(folder);
+    password.set_tags(
+        &child_text(entry, "Tags")
+            .unwrap_or_default()
+            .split([';', ','])
+            .collect::<Vec<_>>(),
+    )?;
+    set_field(&mut password, FIELD_URL, &url, false);
+
+    for (key, value, concealed) in strings {
+        match key {
+            "Title" | "UserName" | "Password" | "URL" | "Notes" => (),
+            "otp" => set_totp(&mut password, value),
+            _ => set_field(&mut password, key, value, concealed),
+        }
+    }
+
+    Ok(password)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|child| child.text())
+}
+
+#[derive(Deserialize)]
+struct BitwardenExport {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<BitwardenFolder>,
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenFolder {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenItem {
+    #[serde(rename = "type")]
+    kind: i32,
+    name: String,
+    folder_id: Option<String>,
+    notes: Option<String>,
+    #[serde(default)]
+    fields: Vec<BitwardenField>,
+    login: Option<BitwardenLogin>,
+    card: Option<BitwardenCard>,
+    identity: Option<HashMap<String, Option<String>>>,
+    ssh_key: Option<BitwardenSshKey>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenField {
+    name: Option<String>,
+    value: Option<String>,
+    #[serde(rename = "type")]
+    kind: i32,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+    #[serde(default)]
+    uris: Vec<BitwardenUri>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenCard {
+    cardholder_name: Option<String>,
+    brand: Option<String>,
+    number: Option<String>,
+    exp_month: Option<String>,
+    exp_year: Option<String>,
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenSshKey {
+    private_key: Option<String>,
+    public_key: Option<String>,
+}
+
+const BITWARDEN_LOGIN: i32 = 1;
+const BITWARDEN_CARD: i32 = 3;
+const BITWARDEN_IDENTITY: i32 = 4;
+const BITWARDEN_SSH_KEY: i32 = 5;
+const BITWARDEN_HIDDEN_FIELD: i32 = 1;
+const BITWARDEN_LINKED_FIELD: i32 = 3;
+
+fn bitwarden_json(contents: &str) -> Result<Vec<Password>, Error> {
+    let export: BitwardenExport =
+        serde_json::from_str(contents).map_err(|err| Error::BadImport(err.to_string()))?;
+
+    if export.encrypted {
+        return Err(Error::BadImport(
+            "encrypted Bitwarden exports are not supported, export as unencrypted JSON".to_string(),
+        ));
+    }
+
+    let folders = export
+        .folders
+        .into_iter()
+        .map(|folder| (folder.id, folder.name))
+        .collect::<HashMap<_, _>>();
+
+    Ok(export
+        .items
+        .into_iter()
+        .map(|item| read_bitwarden_item(item, &folders))
+        .collect())
+}
+
+fn read_bitwarden_item(item: BitwardenItem, folders: &HashMap<String, String>) -> Password {
+    let text = |value: Option<String>| value.unwrap_or_default();
+    let mut password = Password::new(String::new(), item.name, String::new());
+
+    match (
+        item.kind,
+        item.login,
+        item.card,
+        item.identity,
+        item.ssh_key,
+    ) {
+        (BITWARDEN_LOGIN, Some(login), ..) => {
+            let mut uris = login.uris.into_iter().filter_map(|uri| uri.uri);
+
+            if let Some(url) = uris.next() {
+                password.place = place_for(&password.place, &url);
+                set_field(&mut password, FIELD_URL, &url, false);
+            }
+            for (index, url) in uris.enumerate() {
+                set_field(
+                    &mut password,
+                    &format!("{} {}", FIELD_URL, index + 2),
+                    &url,
+                    false,
+                );
+            }
+
+            password.username = text(login.username);
+            password.password = text(login.password);
+            set_totp(&mut password, &text(login.totp));
+        }
+        (BITWARDEN_CARD, _, Some(card), ..) => {
+            let expiry = match (text(card.exp_month), text(card.exp_year)) {
+                (month, year) if month.is_empty() || year.is_empty() => month + &year,
+                (month, year) => format!("{:0>2}/{}", month, year),
+            };
+
+            password.kind = EntryKind::Card;
+            password.username = text(card.cardholder_name);
+            password.password = text(card.number);
+            set_field(&mut password, "expiry", &expiry, false);
+            set_field(&mut password, "cvv", &text(card.code), true);
+            set_field(&mut password, "brand", &text(card.brand), false);
+        }
+        (BITWARDEN_IDENTITY, _, _, Some(identity), _) => {
+            password.kind = EntryKind::SecureNote;
+
+            for (name, value) in identity {
+                set_field(&mut password, &words(&name), &text(value), false);
+            }
+        }
+        (BITWARDEN_SSH_KEY, .., Some(ssh_key)) => {
+            let public_key = text(ssh_key.public_key);
+
+            // The comment is what follows the key type and the key itself.
+            password.kind = EntryKind::SshKey;
+            password.username = public_key
+                .splitn(3, ' ')
+                .nth(2)
+                .unwrap_or_default()
+                .to_string();
+            password.password = text(ssh_key.private_key);
+            set_field(&mut password, "public key", &public_key, false);
+        }
+        _ => password.kind = EntryKind::SecureNote,
+    }
+
+    password.notes = text(item.notes);
+
+    if let Some(folder) = item.folder_id.and_then(|id| folders.get(&id)) {
+        password.set_folder(folder);
+    }
+
+    for field in item.fields {
+        if field.kind != BITWARDEN_LINKED_FIELD {
+            set_field(
+                &mut password,
+                &text(field.name),
+                &text(field.value),
+                field.kind == BITWARDEN_HIDDEN_FIELD,
+            );
+        }
+    }
+
+    password
+}
+
+/// Splits a camel case name such as `licenseNumber` into lowercase words.
+fn words(name: &str) -> String {
+    let mut words = String::new();
+
+    for char in name.chars() {
+        if char.is_uppercase() {
+            words.push(' ');
+        }
+
+        words.extend(char.to_lowercase());
+    }
+
+    words
+}
+
+fn chrome_csv(contents: &str) -> Result<Vec<Password>, Error> {
+    browser_csv(contents, |column| {
+        let url = column("url")?;
+        let mut password = Password::new(
+            column("username")?,
+            place_for(&column("name")?, &url),
+            column("password")?,
+        );
+
+        password.notes = column("note").unwrap_or_default();
+        set_field(&mut password, FIELD_URL, &url, false);
+
+        Ok(password)
+    })
+}
+
+fn firefox_csv(contents: &str) -> Result<Vec<Password>, Error> {
+    browser_csv(contents, |column| {
+        let url = column("url")?;
+        let mut password = Password::new(
+            column("username")?,
+            place_for("", &url),
+            column("password")?,
+        );
+
+        set_field(&mut password, FIELD_URL, &url, false);
+
+        Ok(password)
+    })
+}
+
+/// Reads a browser export, handing `read` a lookup of the current record's columns by header.
+fn browser_csv<F>(contents: &str, read: F) -> Result<Vec<Password>, Error>
+where
+    F: Fn(&dyn Fn(&str) -> Result<String, Error>) -> Result<Password, Error>,
+{
+    let mut records = csv::parse(contents)?.into_iter();
+    let headers = match records.next() {
+        Some(header) => header.fields,
+        None => return Ok(Vec::new()),
+    };
+
+    records
+        .map(|record| {
+            let column = |name: &str| {
+                headers
+                    .iter()
+                    .position(|header| header == name)
+                    .map(|index| record.fields[index].clone())
+                    .ok_or(Error::NoHeader(name.to_string()))
+            };
+
+            read(&column)
+        })
+        .collect()
+}
+
+/// The entry's title, or the host of its URL for the exports that have no title.
+fn place_for(title: &str, url: &str) -> String {
+    if !title.trim().is_empty() {
+        return title.trim().to_string();
+    }
+
+    match Url::parse(url) {
+        Ok(url) if url.host_str().is_some() => {
+            let host = url.host_str().unwrap_or_default();
+
+            host.strip_prefix("www.").unwrap_or(host).to_string()
+        }
+        _ if !url.trim().is_empty() => url.trim().to_string(),
+        _ => IMPORT_UNTITLED.to_string(),
+    }
+}
+
+fn set_field(password: &mut Password, name: &str, value: &str, concealed: bool) {
+    if !name.is_empty() && !value.is_empty() {
+        password.set_field(CustomField {
+            name: name.to_string(),
+            value: value.to_string(),
+            concealed,
+        });
+    }
+}
+
+/// Stores a one-time password given as an `otpauth://` URI or a bare base32 secret. Anything
+/// else, such as Steam's codes, is kept as a concealed field instead.
+fn set_totp(password: &mut Password, secret: &str) {
+    let secret = secret.trim();
+
+    if secret.is_empty() {
+        return;
+    }
+
+    let totp = if secret.starts_with(TOTP_URI_PREFIX) {
+        Totp::from_uri(secret)
+    } else {
+        let label = format!("{}:{}", password.place, password.username);
+
+        Totp::new(
+            secret,
+            TotpAlgorithm::Sha1,
+            TOTP_DEFAULT_DIGITS,
+            TOTP_DEFAULT_PERIOD,
+            label,
+        )
+    };
+
+    match totp.and_then(|totp| totp.to_uri()) {
+        Ok(uri) => password.totp = uri,
+        Err(_) => set_field(password, "totp", secret, true),
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+
+use crate::consts::{JSON_FORMAT_NAME, JSON_SCHEMA_VERSION};
+use crate::errors::Error;
+use crate::password::{CipherVersion, CustomField, EntryKind, Password};
+
+/// Document written by `export --format json` and read by `import --format json`.
+///
+/// ```json
+/// {
+///   "format": "password-manager",
+///   "version": 1,
+///   "kdf_salt": "base64 salt, or null when the entries are decrypted",
+///   "entries": [
+///     {
+///       "place": "db",
+///       "username": "root",
+///       "password": "...",
+///       "encrypted": true,
+///       "cipher": "aes256-gcm",
+///       "kind": "login",
+///       "folder": "work/servers",
+///       "tags": ["infra"],
+///       "notes": "...",
+///       "totp": "otpauth://totp/...",
+///       "fields": [{ "name": "pin", "value": "...", "concealed": true }],
+///       "created_at": 1700000000,
+///       "updated_at": 1700000000,
+///       "last_used_at": null
+///     }
+///   ]
+/// }
+/// ```
+///
+/// `version` is bumped whenever a change would make older readers misread a document. Adding
+/// optional keys does not bump it, and readers ignore keys they do not know.
+#[derive(Serialize, Deserialize)]
+pub struct JsonExport {
+    pub format: String,
+    pub version: u32,
+    /// Salt of the vault the ciphertexts were sealed in, needed to import them into another vault
+    /// that shares the master key.
+    pub kdf_salt: Option<String>,
+    pub entries: Vec<JsonEntry>,
+}
+
+/// One entry. When `encrypted` is true, the password, notes, TOTP URI and field values are
+/// ciphertexts sealed with `cipher`; otherwise `cipher` is `plaintext`. The timestamps are Unix
+/// seconds, informational only: importing an entry sets new ones.
+#[derive(Serialize, Deserialize)]
+pub struct JsonEntry {
+    pub place: String,
+    pub username: String,
+    pub password: String,
+    pub encrypted: bool,
+    pub cipher: CipherVersion,
+    #[serde(default = "default_kind")]
+    pub kind: EntryKind,
+    #[serde(default)]
+    pub folder: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: String,
+    /// `otpauth://` URI, empty when unset.
+    #[serde(default)]
+    pub totp: String,
+    #[serde(default)]
+    pub fields: Vec<JsonField>,
+    #[serde(default)]
+    pub created_at: i64,
+    #[serde(default)]
+    pub updated_at: i64,
+    #[serde(default)]
+    pub last_used_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JsonField {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub concealed: bool,
+}
+
+/// What `ls --json` prints for each entry. It holds no secrets.
+#[derive(Serialize)]
+pub struct JsonListing {
+    pub place: String,
+    pub username: String,
+    pub kind: EntryKind,
+    pub folder: String,
+    pub tags: Vec<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+fn default_kind() -> EntryKind {
+    EntryKind::Login
+}
+
+impl From<&Password> for JsonEntry {
+    fn from(password: &Password) -> Self {
+        Self {
+            place: password.place.clone(),
+            username: password.username.clone(),
+            password: password.password.clone(),
+            encrypted: password.is_encrypted(),
+            cipher: password.cipher,
+            kind: password.kind,
+            folder: password.folder.clone(),
+            tags: password.tags.clone(),
+            notes: password.notes.clone(),
+            totp: password.totp.clone(),
+            fields: password
+                .fields
+                .iter()
+                .map(|field| JsonField {
+                    name: field.name.clone(),
+                    value: field.value.clone(),
+                    concealed: field.concealed,
+                })
+                .collect(),
+            created_at: password.created_at,
+            updated_at: password.updated_at,
+            last_used_at: password.last_used_at,
+        }
+    }
+}
+
+impl TryFrom<JsonEntry> for Password {
+    type Error = Error;
+
+    fn try_from(entry: JsonEntry) -> Result<Self, Self::Error> {
+        if entry.encrypted != (entry.cipher != CipherVersion::Plaintext) {
+            return Err(Error::BadImport(format!(
+                "the encrypted flag of {} does not match its cipher",
+                entry.place
+            )));
+        }
+
+        let mut password = Password::new(entry.username, entry.place, entry.password);
+
+        password.cipher = entry.cipher;
+        password.kind = entry.kind;
+        password.set_folder(&entry.folder);
+        password.set_tags(&entry.tags)?;
+        password.notes = entry.notes;
+        password.totp = entry.totp;
+
+        for field in entry.fields {
+            password.set_field(CustomField {
+                name: field.name,
+                value: field.value,
+                concealed: field.concealed,
+            });
+        }
+
+        Ok(password)
+    }
+}
+
+impl From<&Password> for JsonListing {
+    fn from(password: &Password) -> Self {
+        Self {
+            place: password.place.clone(),
+            username: password.username.clone(),
+            kind: password.kind,
+            folder: password.folder.clone(),
+            tags: password.tags.clone(),
+            created_at: password.created_at,
+            updated_at: password.updated_at,
+            last_used_at: password.last_used_at,
+        }
+    }
+}
+
+/// Writes passwords as they are, decrypted or not. `kdf_salt` is the vault's salt, given when
+/// any of them is encrypted.
+pub fn export(passwords: &[Password], kdf_salt: Option<String>) -> Result<String, Error> {
+    let export = JsonExport {
+        format: JSON_FORMAT_NAME.to_string(),
+        version: JSON_SCHEMA_VERSION,
+        kdf_salt,
+        entries: passwords.iter().map(JsonEntry::from).collect(),
+    };
+
+    to_string(&export)
+}
+
+/// Reads a document written by `export`, along with the salt the encrypted entries were sealed
+/// with.
+pub fn read(contents: &str) -> Result<Vec<(Password, Option<String>)>, Error> {
+    let export: JsonExport =
+        serde_json::from_str(contents).map_err(|err| Error::BadImport(err.to_string()))?;
+
+    if export.format != JSON_FORMAT_NAME {
+        return Err(Error::BadImport(format!(
+            "unknown format `{}`",
+            export.format
+        )));
+    }
+    if export.version != JSON_SCHEMA_VERSION {
+        return Err(Error::UnsupportedJson(export.version));
+    }
+
+    export
+        .entries
+        .into_iter()
+        .map(|entry| Ok((Password::try_from(entry)?, export.kdf_salt.clone())))
+        .collect()
+}
+
+/// Pretty printed JSON, for `--json` output.
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, Error> {
+    serde_json::to_string_pretty(value).map_err(|err| Error::Other(err.to_string()))
+}
@@ -1,6 +1,13 @@
+pub mod backup;
 pub mod consts;
+pub mod csv;
 pub mod database;
+pub mod dump;
 pub mod errors;
+pub mod import;
+pub mod json;
 pub mod operations;
+pub mod pass;
 pub mod password;
+pub mod totp;
 pub mod utils;
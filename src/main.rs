@@ -1,5 +1,12 @@
 use clap::{Parser, Subcommand};
-use password_manager::{operations, utils::pretty_error};
+use password_manager::{
+    consts::{TOTP_DEFAULT_DIGITS, TOTP_DEFAULT_PERIOD},
+    import::ImportFormat,
+    operations::{self, ExportFormat, OnConflict},
+    password::{EntryKind, GeneratorArgs, SortBy},
+    totp::TotpAlgorithm,
+    utils::{parse_duration, pretty_error},
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -12,18 +19,11 @@ struct Cli {
 enum Commands {
     /// Generate a new password.
     Generate {
-        /// Lenght of the generated password.
-        #[arg(short, long, default_value_t = 12)]
-        length: usize,
-        /// Should the password have special characters.
-        #[arg(long)]
-        no_special: bool,
-        /// Should the password have upper case characters.
-        #[arg(long)]
-        no_uppercase: bool,
-        /// Should the password have numbers.
-        #[arg(long)]
-        no_numbers: bool,
+        #[command(flatten)]
+        generator: GeneratorArgs,
+        /// Generate the password with a saved profile instead of the options above.
+        #[arg(long, conflicts_with = "GeneratorArgs")]
+        profile: Option<String>,
         /// Save the generated password to the database.
         #[arg(short, long, requires_all = ["place", "username"])]
         save: bool,
@@ -36,57 +36,314 @@ enum Commands {
         /// Should the password be encrypted if saved.
         #[arg(short, long)]
         no_encrypt: bool,
-        /// List of characters that should be excluded from the password.
-        #[arg(short, long, default_value_t = String::new())]
-        exclude: String,
+        /// Folder to save the password in (eg. work/servers).
+        #[arg(short, long, requires = "save")]
+        folder: Option<String>,
+        /// Tag to add to the saved password. Can be repeated.
+        #[arg(short, long, requires = "save")]
+        tag: Vec<String>,
     },
     /// Add a new password to the database.
     Add {
         /// Password's place.
         #[arg(short, long)]
         place: String,
-        /// Password's username.
+        /// Password's username. Asked for when missing, if the type has one.
         #[arg(short, long)]
-        username: String,
+        username: Option<String>,
+        /// Type of the entry.
+        #[arg(short = 'k', long = "type", value_enum, default_value_t = EntryKind::Login)]
+        kind: EntryKind,
         /// Should the password be encrypted if saved.
         #[arg(short, long)]
         no_encrypt: bool,
+        /// Folder to save the password in (eg. work/servers).
+        #[arg(short, long)]
+        folder: Option<String>,
+        /// Tag to add to the password. Can be repeated.
+        #[arg(short, long)]
+        tag: Vec<String>,
     },
     /// Edit an already existing password.
     Edit {
         /// Password's place.
         place: String,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
         /// Set whether the updated password should be encrypted or not.
         #[arg(long)]
         no_encrypt: bool,
     },
     /// List all the saved places in the database.
-    Ls,
-    /// Delete a password from the database.
+    Ls {
+        /// Order the places by name or by one of their timestamps.
+        #[arg(short, long, value_enum, default_value_t = SortBy::Place)]
+        sort: SortBy,
+        /// Only list passwords with this tag.
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Only list passwords in this folder or its subfolders.
+        #[arg(short, long)]
+        folder: Option<String>,
+        /// Print the passwords as a tree of folders.
+        #[arg(long)]
+        tree: bool,
+        /// Print the entries as JSON.
+        #[arg(long, conflicts_with = "tree")]
+        json: bool,
+    },
+    /// Move a password to the trash.
     Rm {
         /// Password's place.
         place: String,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
     },
     /// Load a password from the database.
-    #[group(required = true, multiple = false)]
     Load {
         /// Password's place.
+        #[arg(required_unless_present = "all")]
         place: Option<String>,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
         /// Load all paswords
-        #[arg(long)]
+        #[arg(long, conflicts_with_all = ["place", "username"])]
         all: bool,
+        /// Show the values of concealed custom fields.
+        #[arg(short, long)]
+        reveal: bool,
+        /// Print the entries as JSON, concealed fields included.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the current one-time password of an entry, or set it up.
+    Otp {
+        /// Password's place.
+        place: String,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
+        /// Set the one-time password from a base32 secret or an otpauth:// URI.
+        #[arg(long, conflicts_with = "remove")]
+        set: bool,
+        /// Remove the one-time password.
+        #[arg(long)]
+        remove: bool,
+        /// Hash algorithm, when setting a base32 secret.
+        #[arg(long, value_enum, default_value_t = TotpAlgorithm::Sha1, requires = "set")]
+        algorithm: TotpAlgorithm,
+        /// Number of digits, when setting a base32 secret.
+        #[arg(long, default_value_t = TOTP_DEFAULT_DIGITS, requires = "set")]
+        digits: u32,
+        /// Seconds each code stays valid, when setting a base32 secret.
+        #[arg(long, default_value_t = TOTP_DEFAULT_PERIOD, requires = "set")]
+        period: u64,
+    },
+    /// Back the passwords up into an archive encrypted with a passphrase of your choice.
+    Backup {
+        /// Write a plaintext CSV file instead of an encrypted archive.
+        #[arg(long)]
+        plaintext: bool,
+    },
+    /// Restore passwords from a backup. Passwords that already exist are skipped.
+    RestoreBackup {
+        /// Backup file.
+        file: String,
+        /// The backup is a plaintext CSV file.
+        #[arg(long)]
+        plaintext: bool,
     },
-    /// Back the passwords into a CSV file. The passwords are all decrypted.
-    Backup,
     /// Similar to backup, but it just dumps the database contents into a CSV without encrypting or decrypting. Useful for automatic periodic backups.
     DumpDatabase,
     /// Restore passwords from a database dump.
     LoadDump {
         /// Database dump file.
         file: String,
+        /// What to do with entries whose place and username already exist.
+        #[arg(long, value_enum, default_value_t = OnConflict::Ask)]
+        on_conflict: OnConflict,
+        /// Show what would be imported without writing anything.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the passwords in a format other programs can read.
+    Export {
+        /// Output format.
+        #[arg(short, long, value_enum)]
+        format: ExportFormat,
+        /// Write the passwords as stored instead of decrypting them.
+        #[arg(long)]
+        ciphertext: bool,
+        /// File to write to instead of printing, or the password store directory to create.
+        #[arg(short, long, required_if_eq("format", "pass"))]
+        output: Option<String>,
+        /// GPG key to encrypt the password store for. Can be given several times.
+        #[arg(short, long)]
+        recipient: Vec<String>,
+        /// Write the password store files unencrypted.
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Import passwords exported from another password manager, a browser or `export`.
+    Import {
+        /// Exported file, or the root of the password store.
+        file: String,
+        /// Format of the exported file.
+        #[arg(short, long, value_enum)]
+        format: ImportFormat,
+        /// Store the imported passwords without encrypting them.
+        #[arg(short, long)]
+        no_encrypt: bool,
+        /// What to do with entries whose place and username already exist.
+        #[arg(long, value_enum, default_value_t = OnConflict::Ask)]
+        on_conflict: OnConflict,
+        /// Read the password store files as unencrypted text.
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Show how a database dump differs from the vault, without printing any password.
+    Diff {
+        /// Database dump file.
+        file: String,
     },
     /// Initial command to create a database with a key.
     CreateDatabase,
+    /// List the previous values of a password.
+    History {
+        /// Password's place.
+        place: String,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
+    },
+    /// Roll a password back to one of its previous values.
+    Restore {
+        /// Password's place.
+        place: String,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
+        /// Version number as listed by `history`.
+        #[arg(short, long)]
+        version: usize,
+    },
+    /// Replace a password with a generated one, following the profile attached to its place.
+    Rotate {
+        /// Password's place.
+        place: String,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
+        #[command(flatten)]
+        generator: GeneratorArgs,
+        /// Generate the password with a saved profile instead of the options above.
+        #[arg(long, conflicts_with = "GeneratorArgs")]
+        profile: Option<String>,
+    },
+    /// Manage deleted passwords.
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+    /// Manage the automatic backups of the database.
+    Backups {
+        #[command(subcommand)]
+        command: BackupsCommands,
+    },
+    /// Manage saved password generation profiles and the places they apply to.
+    Profiles {
+        #[command(subcommand)]
+        command: ProfilesCommands,
+    },
+    /// Configure how strong saved passwords have to be and show the settings.
+    Strength {
+        /// Least strength score, from 0 (guessed at once) to 4 (very hard to guess).
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=4))]
+        min_score: Option<u8>,
+        /// Refuse to save weaker passwords instead of warning about them.
+        #[arg(long)]
+        refuse: Option<bool>,
+    },
+    /// Change the key and re-encrypt every encrypted password with it.
+    ChangeKey,
+    /// Re-encrypt passwords saved by older versions with the current cipher.
+    MigrateCrypto,
+}
+
+#[derive(Subcommand)]
+enum TrashCommands {
+    /// List the passwords in the trash.
+    Ls,
+    /// Move a password out of the trash.
+    Restore {
+        /// Password's place.
+        place: String,
+        /// Username to pick when the place has several accounts.
+        #[arg(short, long)]
+        username: Option<String>,
+    },
+    /// Permanently delete the passwords in the trash.
+    Empty {
+        /// Only delete passwords trashed longer ago than this (eg. 30d, 12h, 2w).
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupsCommands {
+    /// List the automatic backups, newest first.
+    Ls,
+    /// Replace the database with a backup. The current database is backed up first.
+    Restore {
+        /// Backup id, as listed by `backups ls`.
+        id: String,
+    },
+    /// Configure the automatic backups and show their settings.
+    Config {
+        /// Back up the database after every change.
+        #[arg(long)]
+        enabled: Option<bool>,
+        /// Number of most recent backups to keep.
+        #[arg(long)]
+        keep_last: Option<usize>,
+        /// Number of days to keep the last backup of each day for.
+        #[arg(long)]
+        keep_daily: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfilesCommands {
+    /// List the profiles and the places they are attached to.
+    Ls,
+    /// Save generation options under a name, replacing the profile with that name if any.
+    Save {
+        /// Profile name (eg. work-ldap).
+        name: String,
+        #[command(flatten)]
+        generator: GeneratorArgs,
+    },
+    /// Delete a profile, detaching it from its places.
+    Rm {
+        /// Profile name.
+        name: String,
+    },
+    /// Make the passwords generated for a place follow a profile.
+    Attach {
+        /// Password's place.
+        place: String,
+        /// Profile name.
+        profile: String,
+    },
+    /// Stop a place from following a profile.
+    Detach {
+        /// Password's place.
+        place: String,
+    },
 }
 
 #[async_std::main]
@@ -95,42 +352,135 @@ async fn main() {
 
     let result = match cli.command {
         Commands::Generate {
+            generator,
+            profile,
             save,
-            length,
-            no_special,
-            no_uppercase,
-            no_numbers,
             place,
             username,
             no_encrypt,
-            exclude,
+            folder,
+            tag,
         } => {
             operations::generate(
-                save,
-                length,
-                no_special,
-                no_uppercase,
-                no_numbers,
-                place,
-                username,
-                no_encrypt,
-                exclude,
+                save, generator, profile, place, username, no_encrypt, folder, tag,
             )
             .await
         }
-        Commands::Load { place, all } => operations::load(place, all).await,
+        Commands::Load {
+            place,
+            username,
+            all,
+            reveal,
+            json,
+        } => operations::load(place, username, all, reveal, json).await,
+        Commands::Otp {
+            place,
+            username,
+            set,
+            remove,
+            algorithm,
+            digits,
+            period,
+        } => {
+            if set {
+                operations::set_otp(place, username, algorithm, digits, period).await
+            } else if remove {
+                operations::remove_otp(place, username).await
+            } else {
+                operations::otp(place, username).await
+            }
+        }
         Commands::Add {
             place,
             username,
+            kind,
             no_encrypt,
-        } => operations::add_password(place, username, no_encrypt).await,
-        Commands::Rm { place } => operations::delete(place).await,
-        Commands::Backup => operations::backup().await,
-        Commands::Ls => operations::list().await,
+            folder,
+            tag,
+        } => operations::add_password(place, username, kind, no_encrypt, folder, tag).await,
+        Commands::Rm { place, username } => operations::delete(place, username).await,
+        Commands::Backup { plaintext } => operations::backup(plaintext).await,
+        Commands::RestoreBackup { file, plaintext } => {
+            operations::restore_backup(file, plaintext).await
+        }
+        Commands::Ls {
+            sort,
+            tag,
+            folder,
+            tree,
+            json,
+        } => operations::list(sort, tag, folder, tree, json).await,
         Commands::DumpDatabase => operations::dump_db().await,
-        Commands::Edit { place, no_encrypt } => operations::edit(place, no_encrypt).await,
-        Commands::LoadDump { file } => operations::load_dump(file).await,
+        Commands::Edit {
+            place,
+            username,
+            no_encrypt,
+        } => operations::edit(place, username, no_encrypt).await,
+        Commands::LoadDump {
+            file,
+            on_conflict,
+            dry_run,
+        } => operations::load_dump(file, on_conflict, dry_run).await,
+        Commands::Export {
+            format,
+            ciphertext,
+            output,
+            recipient,
+            plain,
+        } => operations::export(format, ciphertext, output, recipient, plain).await,
+        Commands::Import {
+            file,
+            format,
+            no_encrypt,
+            on_conflict,
+            plain,
+        } => operations::import(file, format, no_encrypt, on_conflict, plain).await,
+        Commands::Diff { file } => operations::diff(file).await,
         Commands::CreateDatabase => operations::create_database().await,
+        Commands::History { place, username } => operations::history(place, username).await,
+        Commands::Restore {
+            place,
+            username,
+            version,
+        } => operations::restore(place, username, version).await,
+        Commands::Rotate {
+            place,
+            username,
+            generator,
+            profile,
+        } => operations::rotate(place, username, generator, profile).await,
+        Commands::Trash { command } => match command {
+            TrashCommands::Ls => operations::list_trash().await,
+            TrashCommands::Restore { place, username } => {
+                operations::restore_from_trash(place, username).await
+            }
+            TrashCommands::Empty { older_than } => operations::empty_trash(older_than).await,
+        },
+        Commands::Backups { command } => match command {
+            BackupsCommands::Ls => operations::list_backups().await,
+            BackupsCommands::Restore { id } => operations::restore_backup_snapshot(id).await,
+            BackupsCommands::Config {
+                enabled,
+                keep_last,
+                keep_daily,
+            } => operations::configure_backups(enabled, keep_last, keep_daily).await,
+        },
+        Commands::Profiles { command } => match command {
+            ProfilesCommands::Ls => operations::list_profiles().await,
+            ProfilesCommands::Save { name, generator } => {
+                operations::save_profile(name, generator).await
+            }
+            ProfilesCommands::Rm { name } => operations::remove_profile(name).await,
+            ProfilesCommands::Attach { place, profile } => {
+                operations::attach_profile(place, profile).await
+            }
+            ProfilesCommands::Detach { place } => operations::detach_profile(place).await,
+        },
+        Commands::Strength { min_score, refuse } => {
+            operations::configure_strength(min_score, refuse).await
+        }
+        Commands::ChangeKey => operations::change_key().await,
+        Commands::MigrateCrypto => operations::migrate_crypto().await,
     };
 
     pretty_error(result);
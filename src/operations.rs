@@ -1,304 +1,1822 @@
 use crate::{
+    backup,
     consts::{
         communications::{
-            ENTER_PASSWORD, GENERATED_PASSWORD, INIT_KEY, NEW_PASSWORD, NEW_PLACE, NEW_USERNAME,
-            OPERATION_CANCELLED, PASSWORD_DELETE_CONFIRMATION, SAVED_PASSWORD, SELECTED_PASSWORD,
+            ALREADY_EXISTS, AUTO_BACKUP_FAILED, BACKED_UP, BACKUP_PASSPHRASE, BITS, CONCEAL_FIELD,
+            CRYPTO_MIGRATED, DELETED_AT, DIFFERENT_IN_DUMP, DRY_RUN, EDIT_FIELDS,
+            EMPTY_TRASH_CONFIRMATION, ENTER_NEW_KEY, ENTER_NOTE, ENTER_PRIVATE_KEY_FILE, ENTER_THE,
+            ENTER_TOTP, ENTROPY, EXPORTED, FIELD_NAME, FIELD_VALUE, GENERATED_PASSWORD,
+            IMPORT_INSERTED, IMPORT_OVERWRITTEN, IMPORT_SKIPPED, INIT_KEY, KEY_CHANGED,
+            MOVED_TO_TRASH, NEVER_USED, NEW_FOLDER, NEW_NOTES, NEW_PASSWORD, NEW_PLACE, NEW_TAGS,
+            NEW_USERNAME, NO_BACKUPS, NO_DIFFERENCES, NO_HISTORY, NO_POLICY, NO_PROFILES,
+            ONLY_IN_DUMP, ONLY_IN_VAULT, OPERATION_CANCELLED, PASSWORD_DELETE_CONFIRMATION,
+            PREVIOUS_DATABASE_SAVED, REMOVE_TOTP_CONFIRMATION, RENAMED_FROM, REPLACED_AT,
+            RESTORED_BACKUP, RESTORED_FROM_TRASH, RESTORED_SNAPSHOT, RESTORED_VERSION,
+            RESTORE_SNAPSHOT_CONFIRMATION, SAVED_PASSWORD, SELECTED_PASSWORD, SKIPPED_EXISTING,
+            SKIPPED_MULTILINE, SKIP_IF_EMPTY, STRENGTH, TOTP_REMOVED, TOTP_SAVED, TOTP_VALID_FOR,
+            TRASH_EMPTY, VERSION, WEAK_PASSWORD,
         },
-        CSV_ENCRYPTED, CSV_PASSWORD, CSV_PLACE, CSV_USERNAME,
+        BACKUP_ARCHIVE_NAME, BACKUP_FILE_NAME, TOTP_URI_PREFIX,
     },
-    database::utils::{create_new_save_file, get_validated_conn},
+    database::{
+        profiles::{GeneratorProfiles, StrengthPolicy},
+        queries::DatabaseInterface,
+        snapshots::{auto_snapshot, find_snapshot, list_snapshots, take_snapshot, SnapshotPolicy},
+        utils::{
+            create_new_save_file, get_kdf_salt, get_save_file_path, get_validated_conn, replace_key,
+        },
+    },
+    dump::{self, Difference},
     errors::Error,
-    password::{Password, PasswordBuildOptions, PasswordBuilder},
-    utils::{ask_bool, ask_question, ask_valid_key, create_backup, find_clomun_index},
+    import::{self, ImportFormat},
+    json::{self, JsonEntry, JsonListing},
+    pass::{self, Gpg, PlainText},
+    password::{
+        estimate_strength, sort_passwords, CustomField, EntryKind, Generator, GeneratorArgs,
+        Password, PasswordBuilder, SortBy, Strength,
+    },
+    totp::{Totp, TotpAlgorithm},
+    utils::{
+        ask_bool, ask_choice, ask_lines, ask_new_backup_passphrase, ask_new_key, ask_question,
+        ask_valid_key, create_backup, format_timestamp, generate_salt, VaultKey,
+    },
 };
+use base64::prelude::{Engine, BASE64_STANDARD};
+use chrono::Utc;
 use rpassword::prompt_password;
-use std::{env, fs, io::Read};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt, fs,
+    io::Read,
+    mem,
+};
 
-pub async fn backup() -> Result<(), Error> {
+pub async fn backup(plaintext: bool) -> Result<(), Error> {
     let mut conn = get_validated_conn().await?;
 
     let key = ask_valid_key(&mut conn).await?;
     let current_dir = env::current_dir().map_err(|_| Error::BadDir)?;
-    let mut passwords = conn.get_all_passwords().await?;
+    let mut passwords = conn.get_active_passwords().await?;
 
     for password in passwords.iter_mut() {
-        if password.is_encrypted() {
-            password.decrypt_password(&key)?;
-        }
+        password.decrypt_password(&key)?;
     }
 
-    create_backup(&current_dir, &passwords)?;
-
-    Ok(())
-}
-
-pub async fn dump_db() -> Result<(), Error> {
-    let mut conn = get_validated_conn().await?;
-    let passwords = conn.get_all_passwords().await?;
-
-    println!(
-        "{},{},{},{}",
-        CSV_PLACE, CSV_USERNAME, CSV_PASSWORD, CSV_ENCRYPTED
-    );
+    if plaintext {
+        create_backup(&current_dir, BACKUP_FILE_NAME, &backup::to_csv(&passwords))?;
+    } else {
+        let passphrase = ask_new_backup_passphrase()?;
+        let archive = backup::seal(&passwords, &passphrase)?;
 
-    for password in passwords {
-        println!("{}", password.dump())
+        create_backup(&current_dir, BACKUP_ARCHIVE_NAME, &archive)?;
     }
 
+    println!("{} {}", BACKED_UP, passwords.len());
+
     Ok(())
 }
 
-pub async fn load_dump(file: String) -> Result<(), Error> {
+/// Adds the passwords of a backup made by `backup`, encrypted with the vault's key. Passwords
+/// that already exist (even in the trash) are left alone.
+pub async fn restore_backup(file: String, plaintext: bool) -> Result<(), Error> {
     let mut conn = get_validated_conn().await?;
-    let mut contents = String::new();
     let current_path = env::current_dir().map_err(|_| Error::BadDir)?;
-    let mut file = fs::File::open(current_path.join(file)).map_err(|_| Error::ReadError)?;
+    let contents = fs::read_to_string(current_path.join(file)).map_err(|_| Error::ReadError)?;
 
-    file.read_to_string(&mut contents)
-        .map_err(|_| Error::ReadError)?;
+    let passwords = if plaintext {
+        backup::from_csv(&contents)?
+    } else {
+        let passphrase = prompt_password(BACKUP_PASSPHRASE).map_err(|_| Error::ReadError)?;
+
+        backup::open(&contents, &passphrase)?
+    };
 
-    let mut lines = contents.trim().split("\n");
-    let header = lines.next();
+    let key = ask_valid_key(&mut conn).await?;
+    let existing = conn
+        .get_all_passwords()
+        .await?
+        .into_iter()
+        .map(|password| (password.place, password.username))
+        .collect::<HashSet<_>>();
+    let (skipped, mut restored): (Vec<_>, Vec<_>) = passwords.into_iter().partition(|password| {
+        existing.contains(&(password.place.clone(), password.username.clone()))
+    });
+
+    for password in restored.iter_mut() {
+        password.encrypt_password(&key)?;
+    }
 
-    let header_parts = header.ok_or(Error::BadDump)?.split(",");
+    conn.begin().await?;
 
-    if header_parts.clone().count() < 4 {
-        return Err(Error::BadHeaders);
+    match insert_passwords(&mut conn, &restored).await {
+        Ok(_) => conn.commit().await?,
+        Err(err) => {
+            conn.rollback().await?;
+            return Err(err);
+        }
     }
 
-    let place_index = find_clomun_index(
-        CSV_PLACE,
-        "Enter `place` column name:",
-        header_parts.clone(),
-    )?;
-    let username_index = find_clomun_index(
-        CSV_USERNAME,
-        "Enter `username` column name:",
-        header_parts.clone(),
-    )?;
-    let password_index = find_clomun_index(
-        CSV_PASSWORD,
-        "Enter `password` column name:",
-        header_parts.clone(),
-    )?;
-    let encrypted_index = find_clomun_index(
-        CSV_ENCRYPTED,
-        "Enter `encrypted` column name:",
-        header_parts,
-    )?;
+    auto_backup(&mut conn).await;
 
-    let mut passwords = Vec::new();
-
-    for (index, line) in lines.enumerate() {
-        let parts = line.split(",").map(|v| v.to_owned()).collect::<Vec<_>>();
-
-        let place = parts
-            .get(place_index)
-            .ok_or(Error::MissingField(CSV_PLACE, index + 2))?
-            .to_owned();
-        let username = parts
-            .get(username_index)
-            .ok_or(Error::MissingField(CSV_ENCRYPTED, index + 2))?
-            .to_owned();
-        let password = parts
-            .get(password_index)
-            .ok_or(Error::MissingField(CSV_PASSWORD, index + 2))?
-            .to_owned();
-        let encrypted = parts
-            .get(encrypted_index)
-            .ok_or(Error::MissingField(CSV_ENCRYPTED, index + 2))?;
-
-        let new_password = Password {
-            place,
-            username,
-            password,
-            encrypted: encrypted.parse::<i32>().map_err(|_| Error::ParsingError)?,
-        };
+    println!("{} {}", RESTORED_BACKUP, restored.len());
 
-        passwords.push(new_password);
+    if !skipped.is_empty() {
+        println!("{} {}", SKIPPED_EXISTING, skipped.len());
+
+        for password in skipped {
+            println!("{}", password.summary());
+        }
     }
 
-    for password in passwords.iter() {
+    Ok(())
+}
+
+async fn insert_passwords(
+    conn: &mut DatabaseInterface,
+    passwords: &[Password],
+) -> Result<(), Error> {
+    for password in passwords {
         conn.insert_password(password).await?;
     }
 
-    println!("Loaded {} passwords.", passwords.len());
-
     Ok(())
 }
 
-pub async fn create_database() -> Result<(), Error> {
-    let key = prompt_password(INIT_KEY).map_err(|_| Error::ReadError)?;
+pub async fn dump_db() -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let passwords = conn.get_active_passwords().await?;
+    let kdf_salt = BASE64_STANDARD.encode(get_kdf_salt(&mut conn).await?);
 
-    create_new_save_file(&key).await?;
+    print!("{}", dump::write(&passwords, &kdf_salt));
 
     Ok(())
 }
 
-pub async fn list() -> Result<(), Error> {
+pub async fn load_dump(file: String, on_conflict: OnConflict, dry_run: bool) -> Result<(), Error> {
     let mut conn = get_validated_conn().await?;
+    let passwords = read_dump(file)?;
+    let mut entries = plan_import(&mut conn, passwords, on_conflict).await?;
 
-    let places = conn
-        .get_all_passwords()
-        .await?
-        .into_iter()
-        .map(|password| password.place)
-        .collect::<Vec<_>>();
+    if dry_run {
+        for entry in entries.iter() {
+            println!("{}", entry);
+        }
 
-    println!("{}", places.join("\n"));
+        print_import_summary(&entries);
+        println!("{}", DRY_RUN);
 
-    Ok(())
-}
+        return Ok(());
+    }
 
-pub async fn edit(place: String, no_encrypt: bool) -> Result<(), Error> {
-    let mut conn = get_validated_conn().await?;
     let key = ask_valid_key(&mut conn).await?;
-    let mut password = Password::from(place, &mut conn).await?;
 
-    if password.is_encrypted() {
-        password.decrypt_password(&key)?;
+    reseal_foreign_passwords(&mut conn, &mut entries, &key).await?;
+
+    conn.begin().await?;
+
+    match apply_import(&mut conn, &entries, &key).await {
+        Ok(_) => conn.commit().await?,
+        Err(err) => {
+            conn.rollback().await?;
+            return Err(err);
+        }
     }
 
-    println!("{}\n{}", SELECTED_PASSWORD, &password);
+    auto_backup(&mut conn).await;
+    print_import_summary(&entries);
 
-    let new_place = ask_question(NEW_PLACE)?;
-    let new_username = ask_question(NEW_USERNAME)?.unwrap_or(password.username.clone());
-    let new_password = ask_question(NEW_PASSWORD)?.unwrap_or(password.password.clone());
+    Ok(())
+}
+
+/// Prints the passwords in a format other programs can read. They are decrypted unless
+/// `ciphertext` is set, in which case they are written as stored, like `dump-database` does.
+pub async fn export(
+    format: ExportFormat,
+    ciphertext: bool,
+    output: Option<String>,
+    recipients: Vec<String>,
+    plain: bool,
+) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let mut passwords = conn.get_active_passwords().await?;
+    let current_path = env::current_dir().map_err(|_| Error::BadDir)?;
+    let output = output.map(|output| current_path.join(output));
+
+    if format == ExportFormat::Pass && ciphertext {
+        return Err(Error::EncryptedPassStore);
+    }
+    if format == ExportFormat::Pass && !plain && recipients.is_empty() {
+        return Err(Error::NoRecipient);
+    }
 
-    if new_place.is_none() {
-        password.username = new_username;
-        password.password = new_password;
+    let kdf_salt = if ciphertext {
+        Some(BASE64_STANDARD.encode(get_kdf_salt(&mut conn).await?))
+    } else {
+        let key = ask_valid_key(&mut conn).await?;
 
-        if no_encrypt && password.is_encrypted() {
+        for password in passwords.iter_mut() {
             password.decrypt_password(&key)?;
         }
-        if !no_encrypt && !password.is_encrypted() {
-            password.encrypt_password(&key);
-        }
 
-        conn.update_password(&password).await?;
-    } else {
-        let mut new_password = Password::new(new_username, new_place.unwrap(), new_password);
+        None
+    };
 
-        if !no_encrypt {
-            new_password.encrypt_password(&key);
+    match format {
+        ExportFormat::Json => {
+            let document = json::export(&passwords, kdf_salt)?;
+
+            match output {
+                Some(output) => fs::write(output, document + "\n").map_err(|_| Error::BadDir)?,
+                None => println!("{}", document),
+            }
         }
+        ExportFormat::Pass => {
+            let dir = output.ok_or(Error::BadDir)?;
+            let skipped = if plain {
+                pass::write_store(&dir, &passwords, &PlainText, &[])?
+            } else {
+                let gpg = Gpg { recipients };
 
-        conn.insert_password(&new_password).await?;
-        conn.delete_password(&password.place).await?;
+                pass::write_store(&dir, &passwords, &gpg, &gpg.recipients)?
+            };
+
+            println!("{} {}", EXPORTED, passwords.len() - skipped.len());
+
+            if !skipped.is_empty() {
+                println!("{}", SKIPPED_MULTILINE);
+
+                for password in skipped {
+                    println!("{}", password.summary());
+                }
+            }
+        }
     }
 
     Ok(())
 }
 
-pub async fn generate(
-    save: bool,
-    length: usize,
-    no_special: bool,
-    no_uppercase: bool,
-    no_numbers: bool,
-    place: Option<String>,
-    username: Option<String>,
+/// Formats `export` writes.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    /// Versioned JSON document, which `import --format json` reads back.
+    Json,
+    /// Password store directory of `pass`, one GPG encrypted file per entry.
+    Pass,
+}
+
+/// Imports the export of another password manager, encrypting the entries unless asked not to.
+pub async fn import(
+    file: String,
+    format: ImportFormat,
     no_encrypt: bool,
-    exclude: String,
+    on_conflict: OnConflict,
+    plain: bool,
 ) -> Result<(), Error> {
-    let options = PasswordBuildOptions {
-        length,
-        use_special: !no_special,
-        use_upper: !no_uppercase,
-        use_numbers: !no_numbers,
-        exclude_char: exclude.chars().collect::<Vec<_>>(),
-    };
+    let mut conn = get_validated_conn().await?;
+    let path = env::current_dir().map_err(|_| Error::BadDir)?.join(file);
+    let passwords = if format == ImportFormat::Pass {
+        let passwords = if plain {
+            pass::read_store(&path, &PlainText)?
+        } else {
+            pass::read_store(
+                &path,
+                &Gpg {
+                    recipients: Vec::new(),
+                },
+            )?
+        };
 
-    if !save {
-        let new_password = PasswordBuilder::generate_password(options);
-        println!("{} {}", GENERATED_PASSWORD, new_password);
+        passwords
+            .into_iter()
+            .map(|password| (password, None))
+            .collect()
     } else {
-        let password_builder = PasswordBuilder::from(username.unwrap(), place.unwrap(), options);
-        let mut new_password: Password = password_builder.into();
-        let mut conn = get_validated_conn().await?;
+        let contents = fs::read_to_string(path).map_err(|_| Error::ReadError)?;
 
-        if !no_encrypt {
-            let key = ask_valid_key(&mut conn).await?;
+        import::parse(format, &contents)?
+    };
+    let mut entries = plan_import(&mut conn, passwords, on_conflict).await?;
 
-            println!("{}\n{}", GENERATED_PASSWORD, new_password);
+    let key = ask_valid_key(&mut conn).await?;
+
+    reseal_foreign_passwords(&mut conn, &mut entries, &key).await?;
 
-            new_password.encrypt_password(&key);
+    for entry in entries.iter_mut() {
+        if no_encrypt {
+            entry.password.seal_totp(&key)?;
         } else {
-            println!("{}\n{}", GENERATED_PASSWORD, new_password);
+            entry.password.encrypt_password(&key)?;
         }
+    }
 
-        conn.insert_password(&new_password).await?;
+    conn.begin().await?;
+
+    match apply_import(&mut conn, &entries, &key).await {
+        Ok(_) => conn.commit().await?,
+        Err(err) => {
+            conn.rollback().await?;
+            return Err(err);
+        }
     }
+
+    auto_backup(&mut conn).await;
+    print_import_summary(&entries);
+
     Ok(())
 }
 
-pub async fn add_password(place: String, username: String, no_encrypt: bool) -> Result<(), Error> {
-    let password = ask_question(ENTER_PASSWORD)?;
+/// Reads the passwords of a dump made by `dump-database`, along with the salt of the vault each
+/// one was dumped from.
+fn read_dump(file: String) -> Result<Vec<(Password, Option<String>)>, Error> {
+    let mut contents = String::new();
+    let current_path = env::current_dir().map_err(|_| Error::BadDir)?;
+    let mut file = fs::File::open(current_path.join(file)).map_err(|_| Error::ReadError)?;
 
-    if password.is_none() {
-        return Err(Error::EmptyInput);
-    }
+    file.read_to_string(&mut contents)
+        .map_err(|_| Error::ReadError)?;
+
+    dump::read(&contents)
+}
 
-    let mut new_password = Password::new(username, place, password.unwrap());
+/// Compares the vault with a dump. The key is only asked for when some entries match.
+pub async fn diff(file: String) -> Result<(), Error> {
     let mut conn = get_validated_conn().await?;
+    let dumped = read_dump(file)?;
+    let stored = conn.get_active_passwords().await?;
+    let matches = dump::match_entries(stored, dumped);
 
-    if !no_encrypt {
+    let diff = if matches.pairs.is_empty() {
+        matches.compare(|_, _, _| Ok(()))?
+    } else {
         let key = ask_valid_key(&mut conn).await?;
+        let vault_salt = BASE64_STANDARD.encode(get_kdf_salt(&mut conn).await?);
+        let mut foreign_keys = HashMap::new();
 
-        println!("{}\n{}", SAVED_PASSWORD, new_password);
+        matches.compare(|stored, password, kdf_salt| {
+            let dump_key = dump_key(&key, &vault_salt, password, kdf_salt, &mut foreign_keys)?;
 
-        new_password.encrypt_password(&key);
-    } else {
-        println!("{}\n{}", SAVED_PASSWORD, new_password);
+            password.decrypt_password(dump_key)?;
+            stored.decrypt_password(&key)
+        })?
+    };
+
+    if diff.is_empty() {
+        println!("{}", NO_DIFFERENCES);
+
+        return Ok(());
     }
 
-    conn.insert_password(&new_password).await?;
+    if !diff.only_in_dump.is_empty() {
+        println!("{}", ONLY_IN_DUMP);
+
+        for password in diff.only_in_dump {
+            println!("\t{}", password.summary());
+        }
+    }
+    if !diff.only_in_vault.is_empty() {
+        println!("{}", ONLY_IN_VAULT);
+
+        for password in diff.only_in_vault {
+            println!("\t{}", password.summary());
+        }
+    }
+    if !diff.changed.is_empty() {
+        println!("{}", DIFFERENT_IN_DUMP);
+
+        for (password, differences) in diff.changed {
+            let differences = differences
+                .iter()
+                .map(Difference::to_string)
+                .collect::<Vec<_>>();
+
+            println!("\t{}: {}", password.summary(), differences.join(", "));
+        }
+    }
 
     Ok(())
 }
 
-pub async fn load(place: Option<String>, all: bool) -> Result<(), Error> {
-    let mut conn = get_validated_conn().await?;
+/// What `load-dump` does with an entry whose place and username are already taken.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OnConflict {
+    Skip,
+    Overwrite,
+    Rename,
+    Ask,
+}
 
-    if all {
-        let valid_key = ask_valid_key(&mut conn).await?;
-        let all_passwords = conn.get_all_passwords().await?;
+impl OnConflict {
+    const RESOLUTIONS: [OnConflict; 3] =
+        [OnConflict::Skip, OnConflict::Overwrite, OnConflict::Rename];
 
-        for (index, mut password) in all_passwords.into_iter().enumerate() {
-            if password.is_encrypted() {
-                password.decrypt_password(&valid_key)?;
-            }
+    fn label(&self) -> &'static str {
+        match self {
+            OnConflict::Skip => "skip",
+            OnConflict::Overwrite => "overwrite",
+            OnConflict::Rename => "rename",
+            OnConflict::Ask => "ask",
+        }
+    }
+}
+
+/// What importing a row does to the vault.
+pub enum ImportAction {
+    Insert,
+    /// Replaces the stored password, kept here as it was before the import.
+    Overwrite(Box<Password>),
+    Skip,
+}
 
-            println!("{}:\n{}", index, password)
+/// One row of a dump and what importing it will do.
+pub struct ImportEntry {
+    pub password: Password,
+    pub kdf_salt: Option<String>,
+    pub action: ImportAction,
+    /// Place of the row in the dump, when it was renamed to avoid a conflict.
+    pub renamed_from: Option<String>,
+}
+
+impl fmt::Display for ImportEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            ImportAction::Insert => write!(f, "insert    {}", self.password.summary())?,
+            ImportAction::Overwrite(_) => write!(f, "overwrite {}", self.password.summary())?,
+            ImportAction::Skip => write!(f, "skip      {}", self.password.summary())?,
         }
-    } else {
-        let mut loaded_password = Password::from(place.unwrap(), &mut conn).await?;
 
-        if loaded_password.is_encrypted() {
-            let valid_key = ask_valid_key(&mut conn).await?;
+        match &self.renamed_from {
+            Some(place) => write!(f, " {} {}", RENAMED_FROM, place),
+            None => Ok(()),
+        }
+    }
+}
 
-            loaded_password.decrypt_password(&valid_key)?;
+/// Which entry holds a place and username: a stored password, or an earlier row of the dump.
+enum TakenBy {
+    Stored(Box<Password>),
+    Row(usize),
+}
+
+/// Decides what to do with every row of a dump. Rows clash with stored passwords, trashed ones
+/// included, and with earlier rows of the same dump.
+pub async fn plan_import(
+    conn: &mut DatabaseInterface,
+    passwords: Vec<(Password, Option<String>)>,
+    on_conflict: OnConflict,
+) -> Result<Vec<ImportEntry>, Error> {
+    let mut taken = conn
+        .get_all_passwords()
+        .await?
+        .into_iter()
+        .map(|password| {
+            (
+                (password.place.clone(), password.username.clone()),
+                TakenBy::Stored(Box::new(password)),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let mut entries: Vec<ImportEntry> = Vec::new();
+
+    for (mut password, kdf_salt) in passwords {
+        let key = (password.place.clone(), password.username.clone());
+        let mut renamed_from = None;
+
+        let action = match taken.get(&key) {
+            None => ImportAction::Insert,
+            Some(taken_by) => match resolve_conflict(&password, on_conflict)? {
+                OnConflict::Skip => ImportAction::Skip,
+                OnConflict::Overwrite => match taken_by {
+                    TakenBy::Stored(existing) => ImportAction::Overwrite(existing.clone()),
+                    // The later row wins and takes over what the earlier one was going to do.
+                    TakenBy::Row(index) => {
+                        mem::replace(&mut entries[*index].action, ImportAction::Skip)
+                    }
+                },
+                _ => {
+                    let place = free_place(&password, &taken);
+
+                    renamed_from = Some(mem::replace(&mut password.place, place));
+                    ImportAction::Insert
+                }
+            },
+        };
+
+        if !matches!(action, ImportAction::Skip) {
+            taken.insert(
+                (password.place.clone(), password.username.clone()),
+                TakenBy::Row(entries.len()),
+            );
+        }
+
+        entries.push(ImportEntry {
+            password,
+            kdf_salt,
+            action,
+            renamed_from,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn resolve_conflict(password: &Password, on_conflict: OnConflict) -> Result<OnConflict, Error> {
+    if on_conflict != OnConflict::Ask {
+        return Ok(on_conflict);
+    }
+
+    let options = OnConflict::RESOLUTIONS
+        .iter()
+        .map(|resolution| resolution.label().to_string())
+        .collect::<Vec<_>>();
+    let index = ask_choice(
+        &format!("{} {}", password.summary(), ALREADY_EXISTS),
+        &options,
+    )?;
+
+    Ok(OnConflict::RESOLUTIONS[index])
+}
+
+/// First `place (N)` not yet taken for the password's username.
+fn free_place(password: &Password, taken: &HashMap<(String, String), TakenBy>) -> String {
+    (2..)
+        .map(|number| format!("{} ({})", password.place, number))
+        .find(|place| !taken.contains_key(&(place.clone(), password.username.clone())))
+        .unwrap_or_default()
+}
+
+/// Writes the planned entries, keeping overwritten passwords in the history sealed under `key`.
+/// Callers run it in a transaction, so that a failed row rolls back the whole import.
+pub async fn apply_import(
+    conn: &mut DatabaseInterface,
+    entries: &[ImportEntry],
+    key: &VaultKey,
+) -> Result<(), Error> {
+    for entry in entries {
+        match &entry.action {
+            ImportAction::Insert => conn.insert_password(&entry.password).await?,
+            ImportAction::Overwrite(existing) => {
+                let password = Password {
+                    id: existing.id,
+                    ..entry.password.clone()
+                };
+
+                save_with_history(conn, &password, existing, key).await?;
+
+                if existing.deleted_at.is_some() {
+                    conn.untrash_password(existing.id).await?;
+                }
+            }
+            ImportAction::Skip => (),
         }
+    }
+
+    Ok(())
+}
+
+fn print_import_summary(entries: &[ImportEntry]) {
+    let count = |action: fn(&ImportAction) -> bool| {
+        entries.iter().filter(|entry| action(&entry.action)).count()
+    };
+
+    println!(
+        "{} {}",
+        IMPORT_INSERTED,
+        count(|action| matches!(action, ImportAction::Insert))
+    );
+    println!(
+        "{} {}",
+        IMPORT_OVERWRITTEN,
+        count(|action| matches!(action, ImportAction::Overwrite(_)))
+    );
+    println!(
+        "{} {}",
+        IMPORT_SKIPPED,
+        count(|action| matches!(action, ImportAction::Skip))
+    );
+}
+
+/// Dumped passwords are sealed under the key derived with their vault's salt. The ones dumped from
+/// another vault are re-sealed with this vault's key, which only works when both vaults share the
+/// same master key.
+async fn reseal_foreign_passwords(
+    conn: &mut DatabaseInterface,
+    entries: &mut [ImportEntry],
+    key: &VaultKey,
+) -> Result<(), Error> {
+    let vault_salt = BASE64_STANDARD.encode(get_kdf_salt(conn).await?);
+    let mut foreign_passwords = entries
+        .iter_mut()
+        .filter(|entry| !matches!(entry.action, ImportAction::Skip))
+        .map(|entry| (&mut entry.password, &mut entry.kdf_salt))
+        .filter(|(password, kdf_salt)| {
+            password.is_sealed_with_salt()
+                && kdf_salt.as_ref().is_some_and(|salt| *salt != vault_salt)
+        })
+        .peekable();
+
+    if foreign_passwords.peek().is_none() {
+        return Ok(());
+    }
+
+    let mut foreign_keys = HashMap::new();
+
+    for (password, kdf_salt) in foreign_passwords {
+        let foreign_key = dump_key(
+            key,
+            &vault_salt,
+            password,
+            kdf_salt.as_deref(),
+            &mut foreign_keys,
+        )?;
+
+        password.reseal(foreign_key, key)?;
+    }
+
+    Ok(())
+}
+
+/// Key that opens a dumped password: the vault's own, or the one the same master key gives with
+/// the salt of the vault it was dumped from. The latter are derived once per salt.
+fn dump_key<'a>(
+    key: &'a VaultKey,
+    vault_salt: &str,
+    password: &Password,
+    kdf_salt: Option<&str>,
+    foreign_keys: &'a mut HashMap<String, VaultKey>,
+) -> Result<&'a VaultKey, Error> {
+    let foreign_salt =
+        kdf_salt.filter(|salt| password.is_sealed_with_salt() && *salt != vault_salt);
+
+    match foreign_salt {
+        None => Ok(key),
+        Some(salt) => {
+            if !foreign_keys.contains_key(salt) {
+                let decoded = BASE64_STANDARD.decode(salt).map_err(|_| Error::BadDump)?;
+
+                foreign_keys.insert(salt.to_string(), key.with_salt(&decoded)?);
+            }
 
-        println!("Password:\n{}", loaded_password);
+            Ok(&foreign_keys[salt])
+        }
     }
+}
+
+pub async fn create_database() -> Result<(), Error> {
+    let key = prompt_password(INIT_KEY).map_err(|_| Error::ReadError)?;
+
+    create_new_save_file(&key).await?;
 
     Ok(())
 }
 
-pub async fn delete(place: String) -> Result<(), Error> {
+pub async fn list(
+    sort_by: SortBy,
+    tag: Option<String>,
+    folder: Option<String>,
+    tree: bool,
+    as_json: bool,
+) -> Result<(), Error> {
     let mut conn = get_validated_conn().await?;
-    let password = Password::from(place, &mut conn).await?;
+    let mut passwords = conn
+        .get_active_passwords()
+        .await?
+        .into_iter()
+        .filter(|password| tag.as_ref().is_none_or(|tag| password.has_tag(tag)))
+        .filter(|password| {
+            folder
+                .as_ref()
+                .is_none_or(|folder| password.is_in_folder(folder))
+        })
+        .collect::<Vec<_>>();
 
-    println!("{}\n{}", SELECTED_PASSWORD, &password);
-    let confirmation = ask_bool(PASSWORD_DELETE_CONFIRMATION)?;
+    if tree {
+        passwords.sort_by(|a, b| (&a.folder, &a.place).cmp(&(&b.folder, &b.place)));
+        print_tree(&passwords);
 
-    if confirmation {
-        conn.delete_password(&password.place).await?;
-    } else {
-        println!("{}", OPERATION_CANCELLED);
+        return Ok(());
+    }
+
+    sort_passwords(&mut passwords, sort_by);
+
+    if as_json {
+        let listings = passwords.iter().map(JsonListing::from).collect::<Vec<_>>();
+
+        println!("{}", json::to_string(&listings)?);
+
+        return Ok(());
+    }
+
+    let places = passwords
+        .into_iter()
+        .map(|password| match password.timestamp(sort_by) {
+            Some(timestamp) => format!("{} {}", password.summary(), format_timestamp(timestamp)),
+            None if sort_by == SortBy::LastUsed => format!("{} {}", password.summary(), NEVER_USED),
+            None => password.summary(),
+        })
+        .collect::<Vec<_>>();
+
+    println!("{}", places.join("\n"));
+
+    Ok(())
+}
+
+fn edit_fields(password: &mut Password) -> Result<(), Error> {
+    while let Some(name) = ask_question(FIELD_NAME)? {
+        match ask_question(FIELD_VALUE)? {
+            Some(value) => {
+                let concealed = ask_bool(CONCEAL_FIELD)?;
+
+                password.set_field(CustomField {
+                    name,
+                    value,
+                    concealed,
+                });
+            }
+            None => password.remove_field(&name),
+        }
     }
 
     Ok(())
 }
+
+fn print_tree(passwords: &[Password]) {
+    let mut current_folder: Vec<&str> = Vec::new();
+
+    for password in passwords {
+        let folder = password
+            .folder
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>();
+        let shared_depth = current_folder
+            .iter()
+            .zip(folder.iter())
+            .take_while(|(current, new)| current == new)
+            .count();
+
+        for (depth, part) in folder.iter().enumerate().skip(shared_depth) {
+            println!("{}{}/", "  ".repeat(depth), part);
+        }
+
+        println!("{}{}", "  ".repeat(folder.len()), password.summary());
+
+        current_folder = folder;
+    }
+}
+
+pub async fn edit(place: String, username: Option<String>, no_encrypt: bool) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let key = ask_valid_key(&mut conn).await?;
+    let mut password = Password::from(place, username, &mut conn).await?;
+
+    if password.is_encrypted() {
+        password.decrypt_password(&key)?;
+    }
+
+    println!("{}\n{}", SELECTED_PASSWORD, &password);
+
+    let previous = password.clone();
+    let new_place = ask_question(NEW_PLACE)?;
+    let new_username = ask_question(NEW_USERNAME)?.unwrap_or(password.username.clone());
+    let new_password = ask_question(NEW_PASSWORD)?.unwrap_or(password.password.clone());
+    let new_folder = ask_question(NEW_FOLDER)?;
+    let new_tags = ask_question(NEW_TAGS)?;
+
+    if let Some(new_place) = new_place {
+        password.place = new_place;
+    }
+    if let Some(new_folder) = new_folder {
+        password.set_folder(&new_folder);
+    }
+    if let Some(new_tags) = new_tags {
+        password.set_tags(&new_tags.split(',').collect::<Vec<_>>())?;
+    }
+
+    match ask_question(NEW_NOTES)?.as_deref() {
+        Some("-") => password.notes = String::new(),
+        Some(new_notes) => password.notes = new_notes.to_string(),
+        None => (),
+    }
+
+    if ask_bool(EDIT_FIELDS)? {
+        edit_fields(&mut password)?;
+    }
+    password.username = new_username;
+    password.password = new_password;
+
+    let changed = password.password != previous.password;
+
+    if password.place != previous.place || password.username != previous.username {
+        check_free(&mut conn, &password).await?;
+    }
+
+    if changed {
+        check_entered_password(&mut conn, &password).await?;
+    }
+
+    if !no_encrypt {
+        password.encrypt_password(&key)?;
+    } else {
+        password.seal_totp(&key)?;
+    }
+
+    if changed {
+        update_with_history(&mut conn, &password, &previous, &key).await?;
+    } else {
+        conn.update_password(&password).await?;
+    }
+
+    if password.place != previous.place {
+        GeneratorProfiles::follow_rename(&mut conn, &previous.place, &password.place).await?;
+    }
+
+    auto_backup(&mut conn).await;
+
+    Ok(())
+}
+
+pub async fn history(place: String, username: Option<String>) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let password = Password::from(place, username, &mut conn).await?;
+    let history = conn.get_history(password.id).await?;
+
+    if history.is_empty() {
+        println!("{}", NO_HISTORY);
+
+        return Ok(());
+    }
+
+    let key = ask_valid_key(&mut conn).await?;
+
+    for (index, mut entry) in history.into_iter().enumerate() {
+        entry.password.decrypt_password(&key)?;
+
+        println!(
+            "{} {} ({} {}):\n\tpassword = {}",
+            VERSION,
+            index + 1,
+            REPLACED_AT,
+            format_timestamp(entry.replaced_at),
+            entry.password.password
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn restore(place: String, username: Option<String>, version: usize) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let key = ask_valid_key(&mut conn).await?;
+    let password = Password::from(place, username, &mut conn).await?;
+
+    restore_version(&mut conn, password, version, &key).await?;
+    auto_backup(&mut conn).await;
+
+    println!("{} {}.", RESTORED_VERSION, version);
+
+    Ok(())
+}
+
+/// Puts back the password of the given version of the history, numbered from 1 as `history`
+/// lists them. The replaced password goes to the history in turn.
+pub async fn restore_version(
+    conn: &mut DatabaseInterface,
+    mut password: Password,
+    version: usize,
+    key: &VaultKey,
+) -> Result<(), Error> {
+    let mut history = conn.get_history(password.id).await?;
+
+    if version == 0 || version > history.len() {
+        return Err(Error::NoVersion(version));
+    }
+
+    let mut restored = history.remove(version - 1).password;
+    let was_encrypted = password.is_encrypted();
+
+    restored.decrypt_password(key)?;
+    password.decrypt_password(key)?;
+
+    let previous = password.clone();
+
+    password.password = restored.password;
+
+    if was_encrypted {
+        password.encrypt_password(key)?;
+    } else {
+        password.seal_totp(key)?;
+    }
+
+    update_with_history(conn, &password, &previous, key).await
+}
+
+/// Saves `password` and moves `previous` to the history, in one transaction.
+pub async fn update_with_history(
+    conn: &mut DatabaseInterface,
+    password: &Password,
+    previous: &Password,
+    key: &VaultKey,
+) -> Result<(), Error> {
+    conn.begin().await?;
+
+    match save_with_history(conn, password, previous, key).await {
+        Ok(_) => conn.commit().await,
+        Err(err) => {
+            conn.rollback().await?;
+            Err(err)
+        }
+    }
+}
+
+/// The history is always sealed, even for entries stored in plaintext.
+async fn save_with_history(
+    conn: &mut DatabaseInterface,
+    password: &Password,
+    previous: &Password,
+    key: &VaultKey,
+) -> Result<(), Error> {
+    let mut previous = previous.clone();
+    previous.encrypt_password(key)?;
+
+    conn.insert_history(&previous).await?;
+    conn.update_password(password).await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn generate(
+    save: bool,
+    generator_args: GeneratorArgs,
+    profile: Option<String>,
+    place: Option<String>,
+    username: Option<String>,
+    no_encrypt: bool,
+    folder: Option<String>,
+    tags: Vec<String>,
+) -> Result<(), Error> {
+    // Saved passwords follow the policy of their place, which needs the vault.
+    let generator = if profile.is_some() || save {
+        let mut conn = get_validated_conn().await?;
+        let place = place.as_deref().filter(|_| save);
+
+        GeneratorProfiles::load(&mut conn).await?.pick(
+            place,
+            profile.as_deref(),
+            &generator_args,
+        )?
+    } else {
+        generator_args.generator()?
+    };
+
+    if !save {
+        let new_password = generator.generate()?;
+        println!("{} {}", GENERATED_PASSWORD, new_password);
+        println!("{} {:.1} {}", ENTROPY, generator.entropy(), BITS);
+        println!(
+            "{} {}",
+            STRENGTH,
+            Strength::from_entropy(generator.entropy())
+        );
+    } else {
+        let password_builder =
+            PasswordBuilder::from(username.unwrap(), place.unwrap(), generator.clone());
+        let mut new_password = Password::try_from(password_builder)?;
+        let mut conn = get_validated_conn().await?;
+        let strength = Strength::from_entropy(generator.entropy());
+
+        check_free(&mut conn, &new_password).await?;
+
+        check_strength(&mut conn, &strength).await?;
+
+        new_password.set_folder(&folder.unwrap_or_default());
+        new_password.set_tags(&tags)?;
+
+        if !no_encrypt {
+            let key = ask_valid_key(&mut conn).await?;
+
+            println!("{}\n{}", GENERATED_PASSWORD, new_password);
+
+            new_password.encrypt_password(&key)?;
+        } else {
+            println!("{}\n{}", GENERATED_PASSWORD, new_password);
+        }
+
+        println!("{} {:.1} {}", ENTROPY, generator.entropy(), BITS);
+        println!("{} {}", STRENGTH, strength);
+
+        conn.insert_password(&new_password).await?;
+        auto_backup(&mut conn).await;
+    }
+    Ok(())
+}
+
+/// Replaces the password of an entry with a generated one, following the policy of its place.
+pub async fn rotate(
+    place: String,
+    username: Option<String>,
+    generator_args: GeneratorArgs,
+    profile: Option<String>,
+) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let password = Password::from(place, username, &mut conn).await?;
+    let generator = GeneratorProfiles::load(&mut conn).await?.pick(
+        Some(&password.place),
+        profile.as_deref(),
+        &generator_args,
+    )?;
+    let strength = Strength::from_entropy(generator.entropy());
+
+    check_strength(&mut conn, &strength).await?;
+
+    let key = ask_valid_key(&mut conn).await?;
+    let new_password = rotate_password(&mut conn, password, &generator, &key).await?;
+
+    auto_backup(&mut conn).await;
+
+    println!("{} {}", GENERATED_PASSWORD, new_password);
+    println!("{} {:.1} {}", ENTROPY, generator.entropy(), BITS);
+    println!("{} {}", STRENGTH, strength);
+
+    Ok(())
+}
+
+/// Gives `password` a new password made by `generator`, moving the current one to the history.
+/// The entry stays encrypted or not as it was. Returns the new password.
+pub async fn rotate_password(
+    conn: &mut DatabaseInterface,
+    mut password: Password,
+    generator: &Generator,
+    key: &VaultKey,
+) -> Result<String, Error> {
+    let was_encrypted = password.is_encrypted();
+
+    password.decrypt_password(key)?;
+
+    let previous = password.clone();
+    let new_password = generator.generate()?;
+
+    password.password = new_password.clone();
+
+    if was_encrypted {
+        password.encrypt_password(key)?;
+    } else {
+        password.seal_totp(key)?;
+    }
+
+    update_with_history(conn, &password, &previous, key).await?;
+
+    Ok(new_password)
+}
+
+/// Warns about a password weaker than the vault's policy allows, or refuses it if the policy
+/// says so.
+async fn check_strength(conn: &mut DatabaseInterface, strength: &Strength) -> Result<(), Error> {
+    let policy = StrengthPolicy::load(conn).await?;
+
+    if strength.score >= policy.min_score {
+        return Ok(());
+    }
+    if policy.refuse {
+        return Err(Error::WeakPassword(strength.score, policy.min_score));
+    }
+
+    println!("{} {}/4.", WEAK_PASSWORD, policy.min_score);
+
+    Ok(())
+}
+
+/// Shows the estimated strength of a login's password entered by the user and checks it against
+/// the policy. Other kinds of entries hold secrets the user does not choose.
+async fn check_entered_password(
+    conn: &mut DatabaseInterface,
+    password: &Password,
+) -> Result<(), Error> {
+    if password.kind != EntryKind::Login {
+        return Ok(());
+    }
+
+    let strength = estimate_strength(
+        &password.password,
+        &[password.place.as_str(), password.username.as_str()],
+    );
+
+    println!("{} {}", STRENGTH, strength);
+
+    check_strength(conn, &strength).await
+}
+
+pub async fn add_password(
+    place: String,
+    username: Option<String>,
+    kind: EntryKind,
+    no_encrypt: bool,
+    folder: Option<String>,
+    tags: Vec<String>,
+) -> Result<(), Error> {
+    let mut new_password = ask_entry(place, username, kind)?;
+    let mut conn = get_validated_conn().await?;
+
+    check_free(&mut conn, &new_password).await?;
+    check_entered_password(&mut conn, &new_password).await?;
+
+    new_password.set_folder(&folder.unwrap_or_default());
+    new_password.set_tags(&tags)?;
+
+    if !no_encrypt {
+        let key = ask_valid_key(&mut conn).await?;
+
+        println!("{}\n{}", SAVED_PASSWORD, new_password);
+
+        new_password.encrypt_password(&key)?;
+    } else {
+        println!("{}\n{}", SAVED_PASSWORD, new_password);
+    }
+
+    conn.insert_password(&new_password).await?;
+    auto_backup(&mut conn).await;
+
+    Ok(())
+}
+
+/// Checks that no other entry, trashed ones included, holds the place and username of `password`.
+pub async fn check_free(conn: &mut DatabaseInterface, password: &Password) -> Result<(), Error> {
+    let is_twin = |other: &Password| other.username == password.username && other.id != password.id;
+
+    if let Some(twin) = conn
+        .get_password(&password.place)
+        .await?
+        .into_iter()
+        .find(is_twin)
+    {
+        return Err(Error::PasswordExists(twin.summary()));
+    }
+
+    if let Some(twin) = conn
+        .get_trashed_password(&password.place)
+        .await?
+        .into_iter()
+        .find(is_twin)
+    {
+        return Err(Error::PasswordInTrash(twin.summary()));
+    }
+
+    Ok(())
+}
+
+/// Prompts for the values an entry of `kind` holds, under the labels of the kind.
+fn ask_entry(place: String, username: Option<String>, kind: EntryKind) -> Result<Password, Error> {
+    let (_, username_label, password_label) = kind.labels();
+
+    let username = match (username, username_label) {
+        (Some(username), _) => username,
+        (None, Some(label)) => {
+            ask_question(&format!("{} {}:", ENTER_THE, label))?.ok_or(Error::EmptyInput)?
+        }
+        (None, None) => String::new(),
+    };
+
+    let mut public_key = None;
+    let password = match (kind, password_label) {
+        (EntryKind::SshKey, _) => {
+            let path = ask_question(ENTER_PRIVATE_KEY_FILE)?.ok_or(Error::EmptyInput)?;
+
+            public_key = fs::read_to_string(format!("{}.pub", path)).ok();
+            fs::read_to_string(&path).map_err(|_| Error::ReadError)?
+        }
+        (_, Some(label)) => {
+            ask_question(&format!("{} {}:", ENTER_THE, label))?.ok_or(Error::EmptyInput)?
+        }
+        (_, None) => String::new(),
+    };
+
+    let mut new_password = Password::new(username, place, password.trim_end().to_string());
+
+    new_password.kind = kind;
+
+    if kind == EntryKind::SecureNote {
+        new_password.notes = ask_lines(ENTER_NOTE)?.ok_or(Error::EmptyInput)?;
+    }
+
+    for (name, concealed) in kind.fields() {
+        let value = if *name == "public key" && public_key.is_some() {
+            public_key.take().map(|value| value.trim().to_string())
+        } else if *concealed {
+            let value = prompt_password(format!("{} {} {}: ", ENTER_THE, name, SKIP_IF_EMPTY))
+                .map_err(|_| Error::ReadError)?;
+
+            Some(value.trim().to_string()).filter(|value| !value.is_empty())
+        } else {
+            ask_question(&format!("{} {} {}:", ENTER_THE, name, SKIP_IF_EMPTY))?
+        };
+
+        if let Some(value) = value {
+            new_password.set_field(CustomField {
+                name: name.to_string(),
+                value,
+                concealed: *concealed,
+            });
+        }
+    }
+
+    Ok(new_password)
+}
+
+pub async fn load(
+    place: Option<String>,
+    username: Option<String>,
+    all: bool,
+    reveal: bool,
+    as_json: bool,
+) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+
+    if all {
+        let valid_key = ask_valid_key(&mut conn).await?;
+        let mut all_passwords = conn.get_active_passwords().await?;
+
+        for password in all_passwords.iter_mut() {
+            password.decrypt_password(&valid_key)?;
+            conn.touch_password(password.id).await?;
+        }
+
+        if as_json {
+            let entries = all_passwords
+                .iter()
+                .map(JsonEntry::from)
+                .collect::<Vec<_>>();
+
+            println!("{}", json::to_string(&entries)?);
+
+            return Ok(());
+        }
+
+        for (index, password) in all_passwords.into_iter().enumerate() {
+            if reveal {
+                println!("{}:\n{:#}", index, password)
+            } else {
+                println!("{}:\n{}", index, password)
+            }
+        }
+    } else {
+        let mut loaded_password = Password::from(place.unwrap(), username, &mut conn).await?;
+
+        if loaded_password.is_sealed() {
+            let valid_key = ask_valid_key(&mut conn).await?;
+
+            loaded_password.decrypt_password(&valid_key)?;
+        }
+
+        conn.touch_password(loaded_password.id).await?;
+
+        if as_json {
+            println!("{}", json::to_string(&JsonEntry::from(&loaded_password))?);
+        } else if reveal {
+            println!("Password:\n{:#}", loaded_password);
+        } else {
+            println!("Password:\n{}", loaded_password);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn otp(place: String, username: Option<String>) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let valid_key = ask_valid_key(&mut conn).await?;
+    let mut password = Password::from(place, username, &mut conn).await?;
+
+    if password.totp.is_empty() {
+        return Err(Error::NoTotp(password.place));
+    }
+
+    conn.touch_password(password.id).await?;
+    password.decrypt_password(&valid_key)?;
+
+    let totp = Totp::from_uri(&password.totp)?;
+    let now = Utc::now().timestamp() as u64;
+
+    println!(
+        "{} ({} {}s)",
+        totp.code_at(now),
+        TOTP_VALID_FOR,
+        totp.remaining_at(now)
+    );
+
+    Ok(())
+}
+
+/// Stores the one-time password secret the user enters for a password, either as a base32
+/// secret or as a full `otpauth://` URI. Parameters found in a URI take precedence.
+pub async fn set_otp(
+    place: String,
+    username: Option<String>,
+    algorithm: TotpAlgorithm,
+    digits: u32,
+    period: u64,
+) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let valid_key = ask_valid_key(&mut conn).await?;
+    let mut password = Password::from(place, username, &mut conn).await?;
+    let was_encrypted = password.is_encrypted();
+
+    password.decrypt_password(&valid_key)?;
+
+    let secret = prompt_password(ENTER_TOTP).map_err(|_| Error::ReadError)?;
+    let secret = secret.trim();
+    let totp = if secret.starts_with(TOTP_URI_PREFIX) {
+        Totp::from_uri(secret)?
+    } else {
+        let label = format!("{}:{}", password.place, password.username);
+
+        Totp::new(secret, algorithm, digits, period, label)?
+    };
+
+    password.totp = totp.to_uri()?;
+
+    if was_encrypted {
+        password.encrypt_password(&valid_key)?;
+    } else {
+        password.seal_totp(&valid_key)?;
+    }
+
+    conn.update_password(&password).await?;
+    auto_backup(&mut conn).await;
+    println!("{}", TOTP_SAVED);
+
+    Ok(())
+}
+
+pub async fn remove_otp(place: String, username: Option<String>) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    ask_valid_key(&mut conn).await?;
+    let mut password = Password::from(place, username, &mut conn).await?;
+
+    if password.totp.is_empty() {
+        return Err(Error::NoTotp(password.place));
+    }
+
+    println!("{}\n{}", SELECTED_PASSWORD, &password);
+
+    if !ask_bool(REMOVE_TOTP_CONFIRMATION)? {
+        println!("{}", OPERATION_CANCELLED);
+        return Ok(());
+    }
+
+    password.totp = String::new();
+    conn.update_password(&password).await?;
+    auto_backup(&mut conn).await;
+    println!("{}", TOTP_REMOVED);
+
+    Ok(())
+}
+
+pub async fn delete(place: String, username: Option<String>) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let password = Password::from(place, username, &mut conn).await?;
+
+    println!("{}\n{}", SELECTED_PASSWORD, &password);
+    let confirmation = ask_bool(PASSWORD_DELETE_CONFIRMATION)?;
+
+    if confirmation {
+        conn.trash_password(password.id).await?;
+        auto_backup(&mut conn).await;
+        println!("{}", MOVED_TO_TRASH);
+    } else {
+        println!("{}", OPERATION_CANCELLED);
+    }
+
+    Ok(())
+}
+
+pub async fn change_key() -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let old_key = ask_valid_key(&mut conn).await?;
+    let new_master_key = ask_new_key(ENTER_NEW_KEY)?;
+    let new_salt = generate_salt();
+    let new_key = VaultKey::derive(&new_master_key, &new_salt)?;
+
+    conn.begin().await?;
+
+    let rekeyed = match rekey_vault(&mut conn, &old_key, &new_key, &new_master_key, &new_salt).await
+    {
+        Ok(rekeyed) => {
+            conn.commit().await?;
+            rekeyed
+        }
+        Err(err) => {
+            conn.rollback().await?;
+            return Err(err);
+        }
+    };
+
+    auto_backup(&mut conn).await;
+
+    println!("{} {}", KEY_CHANGED, rekeyed);
+
+    Ok(())
+}
+
+pub async fn migrate_crypto() -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let key = ask_valid_key(&mut conn).await?;
+
+    conn.begin().await?;
+
+    let migrated = match reseal_legacy_rows(&mut conn, &key).await {
+        Ok(migrated) => {
+            conn.commit().await?;
+            migrated
+        }
+        Err(err) => {
+            conn.rollback().await?;
+            return Err(err);
+        }
+    };
+
+    auto_backup(&mut conn).await;
+
+    println!("{} {}", CRYPTO_MIGRATED, migrated);
+
+    Ok(())
+}
+
+/// Re-encrypts the passwords and history rows still sealed with a legacy cipher under the
+/// current one. Returns the number of migrated passwords.
+pub async fn reseal_legacy_rows(
+    conn: &mut DatabaseInterface,
+    key: &VaultKey,
+) -> Result<usize, Error> {
+    let mut passwords = conn
+        .get_all_passwords()
+        .await?
+        .into_iter()
+        .filter(|password| password.cipher.is_legacy())
+        .collect::<Vec<_>>();
+
+    let mut history = conn
+        .get_all_history()
+        .await?
+        .into_iter()
+        .filter(|entry| entry.password.cipher.is_legacy())
+        .collect::<Vec<_>>();
+
+    for password in passwords.iter_mut() {
+        password.decrypt_password(key)?;
+        password.encrypt_password(key)?;
+    }
+
+    for entry in history.iter_mut() {
+        entry.password.decrypt_password(key)?;
+        entry.password.encrypt_password(key)?;
+    }
+
+    save_passwords(conn, &passwords).await?;
+
+    for entry in history.iter() {
+        conn.reencrypt_history(entry).await?;
+    }
+
+    Ok(passwords.len())
+}
+
+async fn save_passwords(conn: &mut DatabaseInterface, passwords: &[Password]) -> Result<(), Error> {
+    for password in passwords {
+        conn.reencrypt_password(password).await?;
+    }
+
+    Ok(())
+}
+
+/// Re-encrypts every encrypted row under `new_key` and stores the new master key. Runs inside
+/// the caller's transaction so the rows can't change between reading and rewriting them.
+/// Returns the number of re-encrypted passwords.
+pub async fn rekey_vault(
+    conn: &mut DatabaseInterface,
+    old_key: &VaultKey,
+    new_key: &VaultKey,
+    new_master_key: &str,
+    new_salt: &[u8],
+) -> Result<usize, Error> {
+    let mut passwords = conn
+        .get_all_passwords()
+        .await?
+        .into_iter()
+        .filter(|password| password.is_sealed())
+        .collect::<Vec<_>>();
+
+    let mut history = conn
+        .get_all_history()
+        .await?
+        .into_iter()
+        .filter(|entry| entry.password.is_encrypted())
+        .collect::<Vec<_>>();
+
+    for password in passwords.iter_mut() {
+        password.reseal(old_key, new_key)?;
+    }
+
+    for entry in history.iter_mut() {
+        entry.password.decrypt_password(old_key)?;
+        entry.password.encrypt_password(new_key)?;
+    }
+
+    save_passwords(conn, &passwords).await?;
+
+    for entry in history.iter() {
+        conn.reencrypt_history(entry).await?;
+    }
+
+    replace_key(new_master_key, new_salt, conn).await?;
+
+    Ok(passwords.len())
+}
+
+pub async fn list_trash() -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+
+    let places = conn
+        .get_trashed_passwords()
+        .await?
+        .into_iter()
+        .map(|password| {
+            format!(
+                "{} {} {}",
+                password.summary(),
+                DELETED_AT,
+                format_timestamp(password.deleted_at.unwrap_or_default())
+            )
+        })
+        .collect::<Vec<_>>();
+
+    println!("{}", places.join("\n"));
+
+    Ok(())
+}
+
+pub async fn restore_from_trash(place: String, username: Option<String>) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let password = Password::from_trash(place, username, &mut conn).await?;
+
+    conn.untrash_password(password.id).await?;
+    auto_backup(&mut conn).await;
+
+    println!("{}\n{}", RESTORED_FROM_TRASH, &password);
+
+    Ok(())
+}
+
+/// Permanently deletes trashed passwords. With `older_than` (in seconds) only the ones trashed
+/// at least that long ago are removed.
+pub async fn empty_trash(older_than: Option<i64>) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let cutoff = Utc::now().timestamp() - older_than.unwrap_or_default();
+    let passwords = conn
+        .get_trashed_passwords()
+        .await?
+        .into_iter()
+        .filter(|password| password.deleted_at.is_some_and(|deleted| deleted <= cutoff))
+        .collect::<Vec<_>>();
+
+    if passwords.is_empty() {
+        println!("{}", TRASH_EMPTY);
+
+        return Ok(());
+    }
+
+    let confirmation = ask_bool(&format!("{} {}", EMPTY_TRASH_CONFIRMATION, passwords.len()))?;
+
+    if !confirmation {
+        println!("{}", OPERATION_CANCELLED);
+
+        return Ok(());
+    }
+
+    conn.begin().await?;
+
+    match delete_passwords(&mut conn, &passwords).await {
+        Ok(_) => conn.commit().await?,
+        Err(err) => {
+            conn.rollback().await?;
+            return Err(err);
+        }
+    }
+
+    auto_backup(&mut conn).await;
+
+    Ok(())
+}
+
+pub async fn list_backups() -> Result<(), Error> {
+    let snapshots = list_snapshots()?;
+
+    if snapshots.is_empty() {
+        println!("{}", NO_BACKUPS);
+
+        return Ok(());
+    }
+
+    for snapshot in snapshots {
+        let size = fs::metadata(&snapshot.path)
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+
+        println!(
+            "{} ({}, {} KiB)",
+            snapshot.id,
+            snapshot.taken_at.format("%Y-%m-%d %H:%M:%S"),
+            size.div_ceil(1024)
+        );
+    }
+
+    Ok(())
+}
+
+/// Replaces the database with a snapshot, after taking a snapshot of the current one.
+pub async fn restore_backup_snapshot(id: String) -> Result<(), Error> {
+    let snapshot = find_snapshot(&id)?;
+
+    if !ask_bool(&format!(
+        "{} {}?",
+        RESTORE_SNAPSHOT_CONFIRMATION, snapshot.id
+    ))? {
+        println!("{}", OPERATION_CANCELLED);
+
+        return Ok(());
+    }
+
+    let mut conn = get_validated_conn().await?;
+    let current = take_snapshot(&mut conn).await?;
+
+    conn.close().await?;
+    fs::copy(&snapshot.path, get_save_file_path()?).map_err(|_| Error::BadDir)?;
+    get_validated_conn().await?;
+
+    println!("{} {}", RESTORED_SNAPSHOT, snapshot.id);
+    println!("{} {}", PREVIOUS_DATABASE_SAVED, current.id);
+
+    Ok(())
+}
+
+pub async fn configure_backups(
+    enabled: Option<bool>,
+    keep_last: Option<usize>,
+    keep_daily: Option<usize>,
+) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let mut policy = SnapshotPolicy::load(&mut conn).await?;
+
+    if let Some(enabled) = enabled {
+        policy.enabled = enabled;
+    }
+    if let Some(keep_last) = keep_last {
+        policy.keep_last = keep_last;
+    }
+    if let Some(keep_daily) = keep_daily {
+        policy.keep_daily = keep_daily;
+    }
+
+    policy.save(&mut conn).await?;
+
+    println!(
+        "\tenabled = {}\n\tkeep_last = {}\n\tkeep_daily = {}",
+        policy.enabled, policy.keep_last, policy.keep_daily
+    );
+
+    Ok(())
+}
+
+/// Snapshots the database after a change, if automatic backups are enabled. The change is
+/// already saved at this point, so a failed snapshot is only reported.
+async fn auto_backup(conn: &mut DatabaseInterface) {
+    if let Err(err) = auto_snapshot(conn).await {
+        println!("{} {}", AUTO_BACKUP_FAILED, err);
+    }
+}
+
+/// Deletes the passwords for good, along with the policies of the places they leave empty.
+async fn delete_passwords(
+    conn: &mut DatabaseInterface,
+    passwords: &[Password],
+) -> Result<(), Error> {
+    for password in passwords {
+        conn.delete_password(password.id).await?;
+    }
+
+    GeneratorProfiles::forget_missing_places(conn).await
+}
+
+pub async fn list_profiles() -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let profiles = GeneratorProfiles::load(&mut conn).await?;
+
+    if profiles.profiles.is_empty() {
+        println!("{}", NO_PROFILES);
+
+        return Ok(());
+    }
+
+    for (name, options) in profiles.profiles.iter() {
+        let places = profiles
+            .policies
+            .iter()
+            .filter(|(_, profile)| *profile == name)
+            .map(|(place, _)| place.as_str())
+            .collect::<Vec<_>>();
+
+        println!("{}: {}", name, options);
+
+        if !places.is_empty() {
+            println!("\tplaces = {}", places.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves the options under `name`, replacing the profile of that name if there is one.
+pub async fn save_profile(name: String, mut options: GeneratorArgs) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let mut profiles = GeneratorProfiles::load(&mut conn).await?;
+
+    options.resolve_wordlist()?;
+    // Fails on options that cannot generate a password.
+    options.generator()?.generate()?;
+
+    println!("{}: {}", name, options);
+
+    profiles.profiles.insert(name, options);
+    profiles.save(&mut conn).await?;
+    auto_backup(&mut conn).await;
+
+    Ok(())
+}
+
+/// Deletes a profile and detaches it from the places that used it.
+pub async fn remove_profile(name: String) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let mut profiles = GeneratorProfiles::load(&mut conn).await?;
+
+    profiles
+        .profiles
+        .remove(&name)
+        .ok_or(Error::NoProfile(name.clone()))?;
+    profiles.policies.retain(|_, profile| *profile != name);
+    profiles.save(&mut conn).await?;
+    auto_backup(&mut conn).await;
+
+    Ok(())
+}
+
+/// Makes `profile` the policy of `place`: passwords generated for it follow the profile.
+pub async fn attach_profile(place: String, profile: String) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let mut profiles = GeneratorProfiles::load(&mut conn).await?;
+
+    profiles.get(&profile)?;
+
+    if conn.get_password(&place).await?.is_empty() {
+        return Err(Error::NoPassword(place));
+    }
+
+    profiles.policies.insert(place, profile);
+    profiles.save(&mut conn).await?;
+    auto_backup(&mut conn).await;
+
+    Ok(())
+}
+
+pub async fn detach_profile(place: String) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let mut profiles = GeneratorProfiles::load(&mut conn).await?;
+
+    if profiles.policies.remove(&place).is_none() {
+        println!("{} {}", NO_POLICY, place);
+
+        return Ok(());
+    }
+
+    profiles.save(&mut conn).await?;
+    auto_backup(&mut conn).await;
+
+    Ok(())
+}
+
+pub async fn configure_strength(min_score: Option<u8>, refuse: Option<bool>) -> Result<(), Error> {
+    let mut conn = get_validated_conn().await?;
+    let mut policy = StrengthPolicy::load(&mut conn).await?;
+
+    if let Some(min_score) = min_score {
+        policy.min_score = min_score;
+    }
+    if let Some(refuse) = refuse {
+        policy.refuse = refuse;
+    }
+
+    policy.save(&mut conn).await?;
+
+    println!(
+        "\tmin_score = {}\n\trefuse = {}",
+        policy.min_score, policy.refuse
+    );
+
+    Ok(())
+}
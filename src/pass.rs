@@ -0,0 +1,353 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use clap::ValueEnum;
+
+use crate::consts::{FIELD_URL, IMPORT_UNTITLED, TAG_SEPARATOR, TOTP_URI_PREFIX};
+use crate::errors::Error;
+use crate::password::{CustomField, EntryKind, Password};
+
+/// File listing the GPG keys a password store is encrypted for.
+const GPG_ID_FILE: &str = ".gpg-id";
+const LOGIN_KEY: &str = "login";
+const TYPE_KEY: &str = "type";
+const TAGS_KEY: &str = "tags";
+/// Keys other programs use for the username, read as the login on import.
+const LOGIN_ALIASES: [&str; 4] = [LOGIN_KEY, "username", "user", "email"];
+
+/// How the files of a password store are sealed. `pass` itself uses GPG; the plain text layer
+/// keeps the same layout unencrypted, for tests and for piping through other tools.
+pub trait StoreEncryption {
+    /// Extension of the entry files, dot included.
+    fn extension(&self) -> &'static str;
+    fn encrypt(&self, contents: &str) -> Result<Vec<u8>, Error>;
+    fn decrypt(&self, contents: &[u8]) -> Result<String, Error>;
+}
+
+/// Encrypts entries for `recipients` with the `gpg` binary, the way `pass insert` does. Decryption
+/// goes through the GPG agent, which asks for the key's passphrase if needed.
+pub struct Gpg {
+    pub recipients: Vec<String>,
+}
+
+impl StoreEncryption for Gpg {
+    fn extension(&self) -> &'static str {
+        ".gpg"
+    }
+
+    fn encrypt(&self, contents: &str) -> Result<Vec<u8>, Error> {
+        let mut args = vec!["--encrypt", "--compress-algo=none", "--no-encrypt-to"];
+
+        for recipient in self.recipients.iter() {
+            args.extend(["--recipient", recipient]);
+        }
+
+        run_gpg(&args, contents.as_bytes())
+    }
+
+    fn decrypt(&self, contents: &[u8]) -> Result<String, Error> {
+        let plaintext = run_gpg(&["--decrypt"], contents)?;
+
+        String::from_utf8(plaintext).map_err(|_| Error::Gpg("the entry is not UTF-8".to_string()))
+    }
+}
+
+fn run_gpg(args: &[&str], input: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut child = Command::new("gpg")
+        .args(["--batch", "--yes", "--quiet", "--output", "-"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::Gpg(err.to_string()))?;
+
+    child
+        .stdin
+        .take()
+        .ok_or(Error::Gpg("could not write to gpg".to_string()))?
+        .write_all(input)
+        .map_err(|err| Error::Gpg(err.to_string()))?;
+
+    let output = child
+        .wait_with_output()
+        .map_err(|err| Error::Gpg(err.to_string()))?;
+
+    if !output.status.success() {
+        return Err(Error::Gpg(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+pub struct PlainText;
+
+impl StoreEncryption for PlainText {
+    fn extension(&self) -> &'static str {
+        ".txt"
+    }
+
+    fn encrypt(&self, contents: &str) -> Result<Vec<u8>, Error> {
+        Ok(contents.as_bytes().to_vec())
+    }
+
+    fn decrypt(&self, contents: &[u8]) -> Result<String, Error> {
+        String::from_utf8(contents.to_vec())
+            .map_err(|_| Error::BadImport("the entry is not UTF-8".to_string()))
+    }
+}
+
+/// Writes decrypted passwords into a new password store at `dir`: one file per entry, under
+/// directories following its folder. A place with several accounts becomes a directory holding a
+/// file per username. Returns the entries that were left out because pass only keeps the first
+/// line of a file as the password.
+pub fn write_store(
+    dir: &Path,
+    passwords: &[Password],
+    encryption: &dyn StoreEncryption,
+    recipients: &[String],
+) -> Result<Vec<Password>, Error> {
+    if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(Error::DirectoryNotEmpty(dir.display().to_string()));
+    }
+
+    fs::create_dir_all(dir).map_err(|_| Error::DirError)?;
+
+    if !recipients.is_empty() {
+        fs::write(dir.join(GPG_ID_FILE), recipients.join("\n") + "\n")
+            .map_err(|_| Error::DirError)?;
+    }
+
+    let (multiline, passwords): (Vec<_>, Vec<_>) = passwords
+        .iter()
+        .partition(|password| password.password.contains('\n'));
+    let mut accounts = HashMap::new();
+
+    for password in passwords.iter() {
+        *accounts
+            .entry((&password.folder, &password.place))
+            .or_insert(0) += 1;
+    }
+
+    let mut written = HashSet::new();
+
+    for password in passwords.iter() {
+        let mut path = dir.to_path_buf();
+
+        for folder in password
+            .folder
+            .split('/')
+            .filter(|folder| !folder.is_empty())
+        {
+            path.push(path_component(folder));
+        }
+
+        path.push(path_component(&password.place));
+
+        if accounts[&(&password.folder, &password.place)] > 1 {
+            path.push(path_component(&password.username));
+        }
+
+        let path = unused_path(path, encryption.extension(), &mut written);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|_| Error::DirError)?;
+        }
+
+        fs::write(&path, encryption.encrypt(&entry_contents(password))?)
+            .map_err(|_| Error::DirError)?;
+    }
+
+    Ok(multiline.into_iter().cloned().collect())
+}
+
+/// Makes a name usable as a single path component that `read_store` does not skip.
+fn path_component(name: &str) -> String {
+    let name = name.trim().replace(['/', '\\', '\0'], "-");
+
+    match name.as_str() {
+        "" | "." | ".." => IMPORT_UNTITLED.to_string(),
+        _ if name.starts_with('.') => format!("_{}", &name[1..]),
+        _ => name,
+    }
+}
+
+/// `path` with `extension` appended, numbered if another entry was already written there.
+fn unused_path(path: PathBuf, extension: &str, written: &mut HashSet<PathBuf>) -> PathBuf {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    (1..)
+        .map(|number| match number {
+            1 => path.with_file_name(format!("{}{}", name, extension)),
+            _ => path.with_file_name(format!("{} ({}){}", name, number, extension)),
+        })
+        .find(|path| written.insert(path.clone()))
+        .unwrap_or_default()
+}
+
+/// The password on the first line, then `key: value` lines, the one-time password URI, and the
+/// notes after a blank line.
+fn entry_contents(password: &Password) -> String {
+    let mut contents = format!("{}\n", password.password);
+
+    if !password.username.is_empty() {
+        contents.push_str(&format!("{}: {}\n", LOGIN_KEY, password.username));
+    }
+    if password.kind != EntryKind::Login {
+        let kind = password
+            .kind
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+
+        contents.push_str(&format!("{}: {}\n", TYPE_KEY, kind));
+    }
+    if !password.tags.is_empty() {
+        contents.push_str(&format!(
+            "{}: {}\n",
+            TAGS_KEY,
+            password.tags.join(TAG_SEPARATOR)
+        ));
+    }
+
+    for field in password.fields.iter() {
+        contents.push_str(&format!(
+            "{}: {}\n",
+            field.name,
+            field.value.replace('\n', " ")
+        ));
+    }
+
+    if !password.totp.is_empty() {
+        contents.push_str(&format!("{}\n", password.totp));
+    }
+    if !password.notes.is_empty() {
+        contents.push_str(&format!("\n{}\n", password.notes));
+    }
+
+    contents
+}
+
+/// Reads every entry of the password store at `dir`. Hidden files and directories, such as
+/// `.gpg-id` and `.git`, are skipped, as are files without the layer's extension.
+pub fn read_store(dir: &Path, encryption: &dyn StoreEncryption) -> Result<Vec<Password>, Error> {
+    let mut files = Vec::new();
+
+    find_entries(dir, encryption.extension(), &mut files)?;
+    files.sort();
+
+    files
+        .into_iter()
+        .map(|file| {
+            let contents = fs::read(&file).map_err(|_| Error::ReadError)?;
+            let relative = file.strip_prefix(dir).map_err(|_| Error::BadDir)?;
+
+            read_entry(
+                relative,
+                encryption.extension(),
+                &encryption.decrypt(&contents)?,
+            )
+        })
+        .collect()
+}
+
+fn find_entries(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(|_| Error::BadDir)? {
+        let path = entry.map_err(|_| Error::BadDir)?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            find_entries(&path, extension, files)?;
+        } else if name.ends_with(extension) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads one entry from its path inside the store and its decrypted contents.
+fn read_entry(relative: &Path, extension: &str, contents: &str) -> Result<Password, Error> {
+    let mut components = relative
+        .iter()
+        .map(|component| component.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let file_name = components.pop().unwrap_or_default();
+    let name = file_name
+        .strip_suffix(extension)
+        .unwrap_or(&file_name)
+        .to_string();
+
+    let mut lines = contents.lines();
+    let mut password = Password::new(
+        String::new(),
+        name.clone(),
+        lines.next().unwrap_or_default().to_string(),
+    );
+    let mut notes = Vec::new();
+
+    for line in lines.by_ref() {
+        if line.starts_with(TOTP_URI_PREFIX) {
+            password.totp = line.to_string();
+            continue;
+        }
+
+        // The first line that is not a `key: value` pair starts the notes.
+        let Some((key, value)) = line
+            .split_once(':')
+            .filter(|(key, _)| !key.trim().is_empty())
+        else {
+            if !line.is_empty() {
+                notes.push(line);
+            }
+            break;
+        };
+        let value = value.trim();
+
+        match key.trim().to_lowercase().as_str() {
+            key if LOGIN_ALIASES.contains(&key) && password.username.is_empty() => {
+                password.username = value.to_string()
+            }
+            TYPE_KEY => {
+                password.kind = EntryKind::from_str(value, true)
+                    .map_err(|_| Error::BadImport(format!("unknown entry type `{}`", value)))?
+            }
+            TAGS_KEY => password.set_tags(&value.split(TAG_SEPARATOR).collect::<Vec<_>>())?,
+            "url" => password.set_field(CustomField {
+                name: FIELD_URL.to_string(),
+                value: value.to_string(),
+                concealed: false,
+            }),
+            _ => password.set_field(CustomField {
+                name: key.trim().to_string(),
+                value: value.to_string(),
+                concealed: false,
+            }),
+        }
+    }
+
+    notes.extend(lines);
+    password.notes = notes.join("\n").trim_end().to_string();
+
+    // `place/username` is the usual layout for places with several accounts.
+    if !password.username.is_empty() && password.username == name && !components.is_empty() {
+        password.place = components.pop().unwrap_or_default();
+    }
+
+    password.set_folder(&components.join("/"));
+
+    Ok(password)
+}
@@ -1,23 +1,127 @@
+use clap::{Args, FromArgMatches};
 use rand::seq::SliceRandom;
-use std::fmt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::{env, fmt, fs};
 
-use crate::consts::{LOWERCASE_CHARACTERS, NUMBERS, SPECIAL_CHARACTERS};
+use crate::consts::communications::SELECT_ACCOUNT;
+use crate::consts::{
+    CONCEALED_VALUE, CRACK_GUESSES_PER_SECOND, CSV_CONCEALED_FIELD_PREFIX, CSV_FIELD_PREFIX,
+    EFF_WORDLIST, LOWERCASE_CHARACTERS, NUMBERS, PASSPHRASE_SEPARATOR, PASSPHRASE_WORDS,
+    PATTERN_MAX_LENGTH, SPECIAL_CHARACTERS, TAG_SEPARATOR, TOTP_URI_PREFIX,
+};
+use crate::csv;
 use crate::database::queries::DatabaseInterface;
 use crate::errors::Error;
-use crate::utils::{decrypt, encrypt};
+use crate::utils::{ask_choice, decrypt, decrypt_legacy, encrypt, VaultKey};
 
 #[derive(sqlx::FromRow, Clone)]
 pub struct Password {
+    pub id: i64,
     pub password: String,
     pub username: String,
     pub place: String,
-    pub encrypted: i32,
+    pub cipher: CipherVersion,
+    pub kind: EntryKind,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_used_at: Option<i64>,
+    pub deleted_at: Option<i64>,
+    /// Slash separated path, empty for passwords outside of any folder.
+    pub folder: String,
+    #[sqlx(skip)]
+    pub tags: Vec<String>,
+    /// Sealed with the same cipher as the password.
+    pub notes: String,
+    #[sqlx(skip)]
+    pub fields: Vec<CustomField>,
+    /// `otpauth://` URI of the one-time password, sealed like the notes. Empty when unset.
+    pub totp: String,
+}
+
+/// Named value stored next to a password, such as a recovery code or an account number.
+/// Concealed fields are masked when the password is displayed, unless revealed explicitly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CustomField {
+    pub name: String,
+    /// Sealed with the same cipher as the password.
+    pub value: String,
+    pub concealed: bool,
+}
+
+/// A previous value of a password, saved whenever it is overwritten. The `password` holds the
+/// entry as it is now, except for the old value and the cipher it is sealed with.
+#[derive(sqlx::FromRow, Clone)]
+pub struct HistoryEntry {
+    pub history_id: i64,
+    pub replaced_at: i64,
+    #[sqlx(flatten)]
+    pub password: Password,
+}
+
+/// Algorithm a stored password was sealed with. Stored as an integer so that the `0`/`1` values of
+/// the old `encrypted` column keep their meaning.
+#[derive(sqlx::Type, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+#[repr(i32)]
+pub enum CipherVersion {
+    Plaintext = 0,
+    MagicCrypt = 1,
+    Aes256Gcm = 2,
+}
+
+/// What an entry holds. Every kind keeps its main values in the place, username and password
+/// columns under its own labels, and its other values as custom fields.
+#[derive(
+    sqlx::Type,
+    clap::ValueEnum,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+)]
+#[serde(rename_all = "kebab-case")]
+#[repr(i32)]
+pub enum EntryKind {
+    Login = 0,
+    SecureNote = 1,
+    Card = 2,
+    SshKey = 3,
+    ApiToken = 4,
+}
+
+/// Field `ls` orders passwords by.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SortBy {
+    Place,
+    Created,
+    Updated,
+    LastUsed,
+}
+
+/// Orders passwords by place, or by the timestamp `sort_by` names, oldest first.
+pub fn sort_passwords(passwords: &mut [Password], sort_by: SortBy) {
+    match sort_by {
+        SortBy::Place => passwords.sort_by(|a, b| a.place.cmp(&b.place)),
+        _ => passwords.sort_by_key(|password| password.timestamp(sort_by)),
+    }
 }
 
 pub struct PasswordBuilder {
     pub username: String,
     pub place: String,
-    options: PasswordBuildOptions,
+    generator: Generator,
+}
+
+/// How a password is generated: characters sampled from a charset, or words from a wordlist.
+#[derive(Clone)]
+pub enum Generator {
+    Characters(PasswordBuildOptions),
+    Passphrase(PassphraseOptions),
+    Pattern(Pattern),
 }
 
 #[derive(Clone)]
@@ -27,124 +131,1209 @@ pub struct PasswordBuildOptions {
     pub use_numbers: bool,
     pub use_upper: bool,
     pub exclude_char: Vec<char>,
+    /// Least number of characters of each class the password holds.
+    pub min_upper: usize,
+    pub min_digits: usize,
+    pub min_special: usize,
+}
+
+#[derive(Clone)]
+pub struct PassphraseOptions {
+    pub words: usize,
+    pub separator: String,
+    pub capitalize: bool,
+    /// Append a random digit to the last word, for sites that require one.
+    pub append_digit: bool,
+    pub wordlist: Vec<String>,
+}
+
+/// A template such as `Aaaa-9999-aaaa`. `a`, `A`, `9` and `!` stand for a random lowercase letter,
+/// uppercase letter, number and special character, `*` for any of them, and `[aA9]` for any
+/// character of the listed classes. `{n}` repeats what comes before it `n` times, `\` makes the
+/// next character literal, and any other character is kept as it is.
+#[derive(Clone)]
+pub struct Pattern {
+    parts: Vec<(PatternPart, usize)>,
+}
+
+#[derive(Clone)]
+enum PatternPart {
+    Literal(char),
+    Class(Vec<char>),
+}
+
+/// How hard a password is to guess.
+pub struct Strength {
+    /// From 0, found within a thousand guesses, to 4, needing more than ten billion, as in zxcvbn.
+    pub score: u8,
+    /// Base 10 logarithm of the number of guesses needed to find the password.
+    pub guesses_log10: f64,
+    /// What makes the password easy to guess, when the estimate can tell.
+    pub warning: Option<String>,
+}
+
+impl Generator {
+    pub fn generate(&self) -> Result<String, Error> {
+        match self {
+            Self::Characters(options) => PasswordBuilder::generate_password(options.clone()),
+            Self::Passphrase(options) => Ok(options.generate()),
+            Self::Pattern(pattern) => Ok(pattern.generate()),
+        }
+    }
+
+    /// Bits of entropy of the generated passwords, from the size of the sets each random choice
+    /// is made from.
+    pub fn entropy(&self) -> f64 {
+        match self {
+            Self::Characters(options) => options.entropy(),
+            Self::Passphrase(options) => options.entropy(),
+            Self::Pattern(pattern) => pattern.entropy(),
+        }
+    }
+}
+
+impl PasswordBuildOptions {
+    /// Bits of entropy of the generated passwords. The characters required by the minimums only
+    /// count for their own class, and where they land is left out, so this slightly understates
+    /// it.
+    pub fn entropy(&self) -> f64 {
+        let classes = PasswordBuilder::build_classes(self);
+        let char_set = classes
+            .iter()
+            .map(|(chars, _, _)| chars.len())
+            .sum::<usize>();
+        let required = classes.iter().map(|(_, minimum, _)| minimum).sum::<usize>();
+        let free = options_log2(char_set) * self.length.saturating_sub(required) as f64;
+
+        classes
+            .iter()
+            .map(|(chars, minimum, _)| options_log2(chars.len()) * *minimum as f64)
+            .sum::<f64>()
+            + free
+    }
+}
+
+impl Strength {
+    /// Strength of a password picked at random among `2^entropy` possibilities. On average it is
+    /// found after trying half of them.
+    pub fn from_entropy(entropy: f64) -> Self {
+        let guesses_log10 = (entropy - 1.0).max(0.0) * 2_f64.log10();
+
+        Self {
+            score: score_for(guesses_log10),
+            guesses_log10,
+            warning: None,
+        }
+    }
+
+    /// Time to find the password offline, when the vault's key derivation limits an attacker to
+    /// `CRACK_GUESSES_PER_SECOND`.
+    pub fn crack_time(&self) -> String {
+        let seconds = 10_f64.powf(self.guesses_log10) / CRACK_GUESSES_PER_SECOND;
+        let units = [
+            ("year", 365.25 * 24.0 * 60.0 * 60.0),
+            ("month", 30.44 * 24.0 * 60.0 * 60.0),
+            ("day", 24.0 * 60.0 * 60.0),
+            ("hour", 60.0 * 60.0),
+            ("minute", 60.0),
+            ("second", 1.0),
+        ];
+
+        if seconds >= 100.0 * units[0].1 {
+            return "centuries".to_string();
+        }
+
+        match units.iter().find(|(_, length)| seconds >= *length) {
+            Some((unit, length)) => {
+                let amount = (seconds / length).round();
+
+                format!(
+                    "{} {}{}",
+                    amount,
+                    unit,
+                    if amount == 1.0 { "" } else { "s" }
+                )
+            }
+            None => "less than a second".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Strength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/4, {} to crack", self.score, self.crack_time())?;
+
+        if let Some(warning) = self.warning.as_ref() {
+            write!(f, " ({})", warning)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Estimates the strength of a password chosen by a person. Unlike entropy, it accounts for the
+/// patterns people use: dictionary words, names, dates, keyboard walks, repeats and sequences.
+/// `user_inputs`, such as the place and username, are guessed first too.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> Strength {
+    let estimate = zxcvbn::zxcvbn(password, user_inputs);
+
+    Strength {
+        score: estimate.score().into(),
+        guesses_log10: estimate.guesses_log10(),
+        warning: estimate
+            .feedback()
+            .and_then(|feedback| feedback.warning())
+            .map(|warning| warning.to_string()),
+    }
+}
+
+/// Score of zxcvbn for a number of guesses.
+fn score_for(guesses_log10: f64) -> u8 {
+    [3.0, 6.0, 8.0, 10.0]
+        .iter()
+        .take_while(|threshold| guesses_log10 >= **threshold)
+        .count() as u8
+}
+
+fn options_log2(options: usize) -> f64 {
+    (options.max(1) as f64).log2()
+}
+
+impl PassphraseOptions {
+    /// Options picking words from `wordlist`, in the format `parse_wordlist` reads.
+    pub fn new(
+        words: usize,
+        separator: String,
+        capitalize: bool,
+        append_digit: bool,
+        wordlist: &str,
+    ) -> Result<Self, Error> {
+        if words == 0 {
+            return Err(Error::ImpossiblePassword(
+                "a passphrase needs at least one word".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            words,
+            separator,
+            capitalize,
+            append_digit,
+            wordlist: parse_wordlist(wordlist)?,
+        })
+    }
+
+    pub fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+        let mut words = (0..self.words)
+            .map(|_| {
+                let word = self.wordlist.choose(&mut rng).cloned().unwrap_or_default();
+
+                if self.capitalize {
+                    capitalize(&word)
+                } else {
+                    word
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if self.append_digit {
+            if let Some(word) = words.last_mut() {
+                word.extend(NUMBERS.chars().collect::<Vec<_>>().choose(&mut rng));
+            }
+        }
+
+        words.join(&self.separator)
+    }
+
+    /// Bits of entropy of the generated passphrases. Only the random choices count, so it depends
+    /// on the size of the wordlist but not on the separator or the capitalisation.
+    pub fn entropy(&self) -> f64 {
+        let mut entropy = self.words as f64 * (self.wordlist.len() as f64).log2();
+
+        if self.append_digit {
+            entropy += (NUMBERS.len() as f64).log2();
+        }
+
+        entropy
+    }
+}
+
+impl Pattern {
+    /// Parses a template, leaving the `exclude` characters out of its classes.
+    pub fn parse(pattern: &str, exclude: &[char]) -> Result<Self, Error> {
+        let mut parts: Vec<(PatternPart, usize)> = Vec::new();
+        let mut repeated = false;
+        let mut chars = pattern.chars();
+
+        while let Some(symbol) = chars.next() {
+            let part = match symbol {
+                '\\' => PatternPart::Literal(chars.next().ok_or(Error::BadPattern(
+                    "it ends in the middle of an escape".to_string(),
+                ))?),
+                '[' => {
+                    let symbols = read_until(&mut chars, ']')?;
+                    let mut set = Vec::new();
+
+                    if symbols.is_empty() {
+                        return Err(Error::BadPattern("`[]` lists no class".to_string()));
+                    }
+
+                    for symbol in symbols.chars() {
+                        let class = pattern_class(symbol).ok_or(Error::BadPattern(format!(
+                            "`{}` inside `[]` is not a character class",
+                            symbol
+                        )))?;
+
+                        for char in class.chars() {
+                            if !set.contains(&char) {
+                                set.push(char);
+                            }
+                        }
+                    }
+
+                    PatternPart::Class(set)
+                }
+                '{' => {
+                    let count = read_until(&mut chars, '}')?;
+                    let count = count.parse::<usize>().map_err(|_| {
+                        Error::BadPattern(format!("`{{{}}}` is not a repetition count", count))
+                    })?;
+
+                    match parts.last_mut() {
+                        Some((_, repeat)) if !repeated => *repeat = count,
+                        _ => {
+                            return Err(Error::BadPattern(format!(
+                                "`{{{}}}` has nothing to repeat",
+                                count
+                            )))
+                        }
+                    }
+
+                    repeated = true;
+                    continue;
+                }
+                ']' | '}' => {
+                    return Err(Error::BadPattern(format!(
+                        "unmatched `{}`, write `\\{}` for the character itself",
+                        symbol, symbol
+                    )))
+                }
+                symbol => match pattern_class(symbol) {
+                    Some(class) => PatternPart::Class(class.chars().collect()),
+                    None => PatternPart::Literal(symbol),
+                },
+            };
+
+            parts.push((part, 1));
+            repeated = false;
+        }
+
+        for (part, _) in parts.iter_mut() {
+            if let PatternPart::Class(chars) = part {
+                chars.retain(|char| !exclude.contains(char));
+
+                if chars.is_empty() {
+                    return Err(Error::ImpossiblePassword(
+                        "every character of a class in the pattern is excluded".to_string(),
+                    ));
+                }
+            }
+        }
+
+        let length = parts.iter().map(|(_, count)| count).sum::<usize>();
+
+        if length == 0 {
+            return Err(Error::BadPattern(
+                "it produces an empty password".to_string(),
+            ));
+        }
+        if length > PATTERN_MAX_LENGTH {
+            return Err(Error::BadPattern(format!(
+                "it produces {} characters, more than the limit of {}",
+                length, PATTERN_MAX_LENGTH
+            )));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Bits of entropy of the generated passwords. Literals add none.
+    pub fn entropy(&self) -> f64 {
+        self.parts
+            .iter()
+            .map(|(part, count)| match part {
+                PatternPart::Literal(_) => 0.0,
+                PatternPart::Class(chars) => options_log2(chars.len()) * *count as f64,
+            })
+            .sum()
+    }
+
+    pub fn generate(&self) -> String {
+        let mut rng = rand::thread_rng();
+        let mut result = String::new();
+
+        for (part, count) in self.parts.iter() {
+            for _ in 0..*count {
+                match part {
+                    PatternPart::Literal(char) => result.push(*char),
+                    PatternPart::Class(chars) => result.extend(chars.choose(&mut rng)),
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Characters of the class a pattern symbol stands for.
+fn pattern_class(symbol: char) -> Option<String> {
+    let upper = LOWERCASE_CHARACTERS.to_uppercase();
+
+    match symbol {
+        'a' => Some(LOWERCASE_CHARACTERS.to_string()),
+        'A' => Some(upper),
+        '9' => Some(NUMBERS.to_string()),
+        '!' => Some(SPECIAL_CHARACTERS.to_string()),
+        '*' => Some([LOWERCASE_CHARACTERS, &upper, NUMBERS, SPECIAL_CHARACTERS].concat()),
+        _ => None,
+    }
+}
+
+/// Reads the characters up to `end`, which is consumed.
+fn read_until(chars: &mut std::str::Chars, end: char) -> Result<String, Error> {
+    let mut read = String::new();
+
+    for char in chars.by_ref() {
+        if char == end {
+            return Ok(read);
+        }
+
+        read.push(char);
+    }
+
+    Err(Error::BadPattern(format!("a `{}` is missing", end)))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Reads a wordlist with one word per line. Lines may start with their dice roll, as in the EFF
+/// and diceware lists; blank lines, `#` comments and repeated words are skipped.
+pub fn parse_wordlist(contents: &str) -> Result<Vec<String>, Error> {
+    let mut words = Vec::new();
+    let mut seen = HashSet::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let word = line
+            .split_whitespace()
+            .last()
+            .unwrap_or_default()
+            .to_string();
+
+        if seen.insert(word.clone()) {
+            words.push(word);
+        }
+    }
+
+    if words.len() < 2 {
+        return Err(Error::BadWordlist(
+            "it needs at least two different words".to_string(),
+        ));
+    }
+
+    Ok(words)
 }
 
 impl PasswordBuilder {
-    pub fn from(username: String, place: String, options: PasswordBuildOptions) -> Self {
+    pub fn from(username: String, place: String, generator: Generator) -> Self {
         Self {
             username,
             place,
-            options,
+            generator,
         }
     }
 
-    fn build_charset(options: &PasswordBuildOptions) -> Vec<char> {
-        let mut char_set = LOWERCASE_CHARACTERS.to_string();
+    /// The enabled character classes with the excluded characters removed, each with the least
+    /// number of its characters the password needs and the flag that sets it.
+    fn build_classes(options: &PasswordBuildOptions) -> Vec<(Vec<char>, usize, &'static str)> {
+        let upper = LOWERCASE_CHARACTERS.to_uppercase();
+        let classes = [
+            (true, LOWERCASE_CHARACTERS, 0, ""),
+            (
+                options.use_upper,
+                upper.as_str(),
+                options.min_upper,
+                "--min-upper",
+            ),
+            (
+                options.use_numbers,
+                NUMBERS,
+                options.min_digits,
+                "--min-digits",
+            ),
+            (
+                options.use_special,
+                SPECIAL_CHARACTERS,
+                options.min_special,
+                "--min-special",
+            ),
+        ];
+
+        classes
+            .into_iter()
+            .map(|(enabled, chars, minimum, flag)| {
+                let chars = chars
+                    .chars()
+                    .filter(|char| enabled && !options.exclude_char.contains(char))
+                    .collect::<Vec<_>>();
+
+                (chars, minimum, flag)
+            })
+            .collect()
+    }
+
+    /// Picks `length` characters from the enabled classes, at least the minimum of each, then
+    /// shuffles them so the required characters are not all at the front.
+    pub fn generate_password(options: PasswordBuildOptions) -> Result<String, Error> {
+        let classes = Self::build_classes(&options);
+        let char_set = classes
+            .iter()
+            .flat_map(|(chars, _, _)| chars.iter().copied())
+            .collect::<Vec<_>>();
 
-        if options.use_upper {
-            char_set.push_str(LOWERCASE_CHARACTERS.to_uppercase().as_str());
+        if char_set.is_empty() && options.length > 0 {
+            return Err(Error::ImpossiblePassword(
+                "every character is excluded".to_string(),
+            ));
         }
-        if options.use_numbers {
-            char_set.push_str(NUMBERS);
+        if let Some((_, _, flag)) = classes
+            .iter()
+            .find(|(chars, minimum, _)| chars.is_empty() && *minimum > 0)
+        {
+            return Err(Error::ImpossiblePassword(format!(
+                "{} asks for characters that are disabled or excluded",
+                flag
+            )));
+        }
+
+        let required = classes.iter().map(|(_, minimum, _)| minimum).sum::<usize>();
+
+        if required > options.length {
+            return Err(Error::ImpossiblePassword(format!(
+                "the minimums add up to {} characters, more than the length of {}",
+                required, options.length
+            )));
         }
-        if options.use_special {
-            char_set.push_str(SPECIAL_CHARACTERS);
+
+        let mut rng = rand::thread_rng();
+        let mut result: Vec<char> = Vec::with_capacity(options.length);
+
+        for (chars, minimum, _) in classes.iter() {
+            result.extend((0..*minimum).filter_map(|_| chars.choose(&mut rng)));
         }
+        result.extend((required..options.length).filter_map(|_| char_set.choose(&mut rng)));
+        result.shuffle(&mut rng);
 
-        char_set
-            .chars()
-            .filter(|char| !options.exclude_char.contains(char))
-            .collect::<Vec<_>>()
+        Ok(result.into_iter().collect())
+    }
+}
+
+impl TryFrom<PasswordBuilder> for Password {
+    type Error = Error;
+
+    fn try_from(builder: PasswordBuilder) -> Result<Self, Self::Error> {
+        let password = builder.generator.generate()?;
+
+        Ok(Password::new(builder.username, builder.place, password))
     }
+}
 
-    pub fn generate_password(options: PasswordBuildOptions) -> String {
-        let char_set = Self::build_charset(&options);
-        let mut result = String::new();
+/// Options of `generate` that decide what the password is made of. Generation profiles store
+/// them as JSON.
+#[derive(clap::Args, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorArgs {
+    /// Lenght of the generated password.
+    #[arg(short, long, default_value_t = 12)]
+    pub length: usize,
+    /// Should the password have special characters.
+    #[arg(long)]
+    pub no_special: bool,
+    /// Should the password have upper case characters.
+    #[arg(long)]
+    pub no_uppercase: bool,
+    /// Should the password have numbers.
+    #[arg(long)]
+    pub no_numbers: bool,
+    /// List of characters that should be excluded from the password.
+    #[arg(short, long, default_value_t = String::new())]
+    pub exclude: String,
+    /// Least number of upper case characters in the password.
+    #[arg(long, default_value_t = 0)]
+    pub min_upper: usize,
+    /// Least number of numbers in the password.
+    #[arg(long, default_value_t = 0)]
+    pub min_digits: usize,
+    /// Least number of special characters in the password.
+    #[arg(long, default_value_t = 0)]
+    pub min_special: usize,
+    /// Template of the password, such as `Aaaa-9999-aaaa`: `a`, `A`, `9` and `!` stand for a
+    /// lowercase letter, an upper case letter, a number and a special character, `*` for any of
+    /// them and `[aA9]` for any of the listed classes. `{n}` repeats the previous character,
+    /// `\` escapes the next one and anything else is kept as is.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "length",
+            "no_special",
+            "no_uppercase",
+            "no_numbers",
+            "min_upper",
+            "min_digits",
+            "min_special",
+            "passphrase",
+        ]
+    )]
+    pub pattern: Option<String>,
+    /// Generate a passphrase of random words instead of characters.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "length",
+            "no_special",
+            "no_uppercase",
+            "no_numbers",
+            "exclude",
+            "min_upper",
+            "min_digits",
+            "min_special",
+        ]
+    )]
+    pub passphrase: bool,
+    /// Number of words in the passphrase.
+    #[arg(
+        long,
+        default_value_t = PASSPHRASE_WORDS,
+        requires = "passphrase",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub words: usize,
+    /// Text put between the words of the passphrase.
+    #[arg(long, default_value_t = PASSPHRASE_SEPARATOR.to_string(), requires = "passphrase")]
+    pub separator: String,
+    /// Start every word of the passphrase with a capital letter.
+    #[arg(long, requires = "passphrase")]
+    pub capitalize: bool,
+    /// Append a random digit to the passphrase.
+    #[arg(long, requires = "passphrase")]
+    pub append_digit: bool,
+    /// Wordlist file to pick the words from instead of the bundled EFF list.
+    #[arg(long, requires = "passphrase")]
+    pub wordlist: Option<String>,
+}
 
-        for _ in 0..options.length {
-            let next_char = char_set.choose(&mut rand::thread_rng()).unwrap();
+impl GeneratorArgs {
+    pub fn generator(&self) -> Result<Generator, Error> {
+        if let Some(pattern) = self.pattern.as_ref() {
+            let exclude = self.exclude.chars().collect::<Vec<_>>();
 
-            result.push_str(next_char.to_string().as_str());
+            return Ok(Generator::Pattern(Pattern::parse(pattern, &exclude)?));
+        }
+        if !self.passphrase {
+            return Ok(Generator::Characters(PasswordBuildOptions {
+                length: self.length,
+                use_special: !self.no_special,
+                use_upper: !self.no_uppercase,
+                use_numbers: !self.no_numbers,
+                exclude_char: self.exclude.chars().collect::<Vec<_>>(),
+                min_upper: self.min_upper,
+                min_digits: self.min_digits,
+                min_special: self.min_special,
+            }));
         }
 
-        result
+        let wordlist = match self.wordlist.as_ref() {
+            Some(wordlist) => {
+                let current_path = env::current_dir().map_err(|_| Error::BadDir)?;
+
+                Some(
+                    fs::read_to_string(current_path.join(wordlist))
+                        .map_err(|_| Error::ReadError)?,
+                )
+            }
+            None => None,
+        };
+
+        Ok(Generator::Passphrase(PassphraseOptions::new(
+            self.words,
+            self.separator.clone(),
+            self.capitalize,
+            self.append_digit,
+            wordlist.as_deref().unwrap_or(EFF_WORDLIST),
+        )?))
+    }
+
+    /// Makes the wordlist path absolute, so a saved profile works from any directory.
+    pub fn resolve_wordlist(&mut self) -> Result<(), Error> {
+        if let Some(wordlist) = self.wordlist.as_mut() {
+            let current_path = env::current_dir().map_err(|_| Error::BadDir)?;
+
+            *wordlist = current_path.join(&wordlist).display().to_string();
+        }
+
+        Ok(())
+    }
+}
+
+/// The options `generate` uses when given none.
+impl Default for GeneratorArgs {
+    fn default() -> Self {
+        Self::augment_args(clap::Command::new("generate"))
+            .try_get_matches_from(["generate"])
+            .and_then(|matches| Self::from_arg_matches(&matches))
+            .expect("the defaults of every option are valid")
+    }
+}
+
+/// The options as `generate` flags, leaving out the ones that do not apply.
+impl fmt::Display for GeneratorArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flags = Vec::new();
+
+        if let Some(pattern) = self.pattern.as_ref() {
+            flags.push(format!("--pattern '{}'", pattern));
+        } else if self.passphrase {
+            flags.push(format!(
+                "--passphrase --words {} --separator '{}'",
+                self.words, self.separator
+            ));
+
+            if self.capitalize {
+                flags.push("--capitalize".to_string());
+            }
+            if self.append_digit {
+                flags.push("--append-digit".to_string());
+            }
+            if let Some(wordlist) = self.wordlist.as_ref() {
+                flags.push(format!("--wordlist '{}'", wordlist));
+            }
+        } else {
+            flags.push(format!("--length {}", self.length));
+
+            for (set, flag) in [
+                (self.no_special, "--no-special"),
+                (self.no_uppercase, "--no-uppercase"),
+                (self.no_numbers, "--no-numbers"),
+            ] {
+                if set {
+                    flags.push(flag.to_string());
+                }
+            }
+            for (minimum, flag) in [
+                (self.min_upper, "--min-upper"),
+                (self.min_digits, "--min-digits"),
+                (self.min_special, "--min-special"),
+            ] {
+                if minimum > 0 {
+                    flags.push(format!("{} {}", flag, minimum));
+                }
+            }
+        }
+
+        if !self.exclude.is_empty() && !self.passphrase {
+            flags.push(format!("--exclude '{}'", self.exclude));
+        }
+
+        write!(f, "{}", flags.join(" "))
+    }
+}
+
+impl CipherVersion {
+    pub const CURRENT: Self = Self::Aes256Gcm;
+
+    pub fn is_legacy(&self) -> bool {
+        *self != Self::Plaintext && *self != Self::CURRENT
+    }
+}
+
+impl CipherVersion {
+    fn decrypt(&self, ciphertext: &str, key: &VaultKey) -> Result<String, Error> {
+        match self {
+            Self::Plaintext => Ok(ciphertext.to_string()),
+            Self::MagicCrypt => decrypt_legacy(ciphertext, key),
+            Self::Aes256Gcm => decrypt(ciphertext, key),
+        }
+    }
+}
+
+impl TryFrom<i32> for CipherVersion {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Plaintext),
+            1 => Ok(Self::MagicCrypt),
+            2 => Ok(Self::Aes256Gcm),
+            other => Err(Error::UnknownCipher(other)),
+        }
+    }
+}
+
+impl EntryKind {
+    /// Labels of the place, username and password columns, `None` for the columns the kind does
+    /// not use.
+    pub fn labels(&self) -> (&'static str, Option<&'static str>, Option<&'static str>) {
+        match self {
+            Self::Login => ("palce", Some("username"), Some("password")),
+            Self::SecureNote => ("title", None, None),
+            Self::Card => ("card", Some("cardholder"), Some("number")),
+            Self::SshKey => ("key", Some("comment"), Some("private key")),
+            Self::ApiToken => ("service", Some("key id"), Some("token")),
+        }
+    }
+
+    /// Custom fields every entry of the kind is prompted for, and whether they are concealed.
+    pub fn fields(&self) -> &'static [(&'static str, bool)] {
+        match self {
+            Self::Login | Self::SecureNote => &[],
+            Self::Card => &[("expiry", false), ("cvv", true), ("pin", true)],
+            Self::SshKey => &[("public key", false), ("passphrase", true)],
+            Self::ApiToken => &[("endpoint", false), ("expires", false)],
+        }
+    }
+}
+
+impl TryFrom<i32> for EntryKind {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Login),
+            1 => Ok(Self::SecureNote),
+            2 => Ok(Self::Card),
+            3 => Ok(Self::SshKey),
+            4 => Ok(Self::ApiToken),
+            other => Err(Error::UnknownKind(other)),
+        }
     }
 }
 
-impl Into<Password> for PasswordBuilder {
-    fn into(self) -> Password {
-        let password = PasswordBuilder::generate_password(self.options);
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Login => "login",
+            Self::SecureNote => "secure note",
+            Self::Card => "card",
+            Self::SshKey => "SSH key",
+            Self::ApiToken => "API token",
+        };
+
+        write!(f, "{}", name)
+    }
+}
 
-        Password::new(self.username, self.place, password)
+impl fmt::Display for CipherVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", *self as i32)
     }
 }
 
 impl fmt::Display for Password {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.is_encrypted() {
-            write!(
-                f,
-                "\tpalce = {}\n\tusername = {}",
-                self.place, self.username
-            )
-        } else {
+        let (place_label, username_label, password_label) = self.kind.labels();
+
+        write!(f, "\t{} = {}", place_label, self.place)?;
+
+        if self.kind != EntryKind::Login {
+            write!(f, "\n\ttype = {}", self.kind)?;
+        }
+        if let Some(label) = username_label {
+            write!(f, "\n\t{} = {}", label, self.username)?;
+        }
+        if let Some(label) = password_label.filter(|_| !self.is_encrypted()) {
             write!(
                 f,
-                "\tpalce = {}\n\tusername = {}\n\tpassword = {}",
-                self.place, self.username, self.password
-            )
+                "\n\t{} = {}",
+                label,
+                self.password.replace('\n', "\n\t\t")
+            )?;
+        }
+        if !self.folder.is_empty() {
+            write!(f, "\n\tfolder = {}", self.folder)?;
+        }
+        if !self.tags.is_empty() {
+            write!(f, "\n\ttags = {}", self.tags.join(", "))?;
+        }
+        if !self.totp.is_empty() {
+            write!(f, "\n\ttotp = enabled")?;
+        }
+        if !self.is_encrypted() {
+            if !self.notes.is_empty() {
+                write!(f, "\n\tnotes = {}", self.notes.replace('\n', "\n\t\t"))?;
+            }
+
+            // The fields of the kind come first, in the order they are prompted for.
+            let kind_fields = self.kind.fields();
+            let mut fields = self.fields.iter().collect::<Vec<_>>();
+            fields.sort_by_key(|field| {
+                kind_fields
+                    .iter()
+                    .position(|(name, _)| *name == field.name)
+                    .unwrap_or(kind_fields.len())
+            });
+
+            for field in fields {
+                if field.concealed && !f.alternate() {
+                    write!(f, "\n\t{} = {}", field.name, CONCEALED_VALUE)?;
+                } else {
+                    write!(f, "\n\t{} = {}", field.name, field.value)?;
+                }
+            }
         }
+
+        Ok(())
     }
 }
 
 impl Password {
     pub fn new(username: String, place: String, password: String) -> Self {
         Self {
+            id: 0,
             password,
             place,
-            encrypted: 0,
+            cipher: CipherVersion::Plaintext,
+            kind: EntryKind::Login,
+            created_at: 0,
+            updated_at: 0,
+            last_used_at: None,
+            deleted_at: None,
+            folder: String::new(),
+            tags: Vec::new(),
+            notes: String::new(),
+            fields: Vec::new(),
+            totp: String::new(),
             username,
         }
     }
 
-    pub async fn from(place: String, conn: &mut DatabaseInterface) -> Result<Self, Error> {
-        let password = conn.get_password(&place).await?;
+    /// Finds the password saved for `place`. If the place has several accounts and `username`
+    /// does not narrow it down to one, the user picks from a list.
+    pub async fn from(
+        place: String,
+        username: Option<String>,
+        conn: &mut DatabaseInterface,
+    ) -> Result<Self, Error> {
+        let passwords = conn.get_password(&place).await?;
 
-        if password.len() == 0 {
-            Err(Error::NoPassword(place))
-        } else {
-            Ok(password.into_iter().nth(0).unwrap())
+        Self::pick(place, username, passwords, |usernames| {
+            ask_choice(SELECT_ACCOUNT, usernames)
+        })
+    }
+
+    /// Same as `from`, but looks for the password among the ones in the trash.
+    pub async fn from_trash(
+        place: String,
+        username: Option<String>,
+        conn: &mut DatabaseInterface,
+    ) -> Result<Self, Error> {
+        let passwords = conn.get_trashed_password(&place).await?;
+
+        Self::pick(place, username, passwords, |usernames| {
+            ask_choice(SELECT_ACCOUNT, usernames)
+        })
+    }
+
+    /// Narrows `passwords` down to the account named `username`, if given. When several accounts
+    /// remain, `choose` gets their usernames and returns the index of the one to use.
+    pub fn pick<F>(
+        place: String,
+        username: Option<String>,
+        passwords: Vec<Password>,
+        choose: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnOnce(&[String]) -> Result<usize, Error>,
+    {
+        let mut passwords = passwords
+            .into_iter()
+            .filter(|password| {
+                username
+                    .as_ref()
+                    .is_none_or(|name| &password.username == name)
+            })
+            .collect::<Vec<_>>();
+
+        match passwords.len() {
+            0 => Err(Error::NoPassword(place)),
+            1 => Ok(passwords.remove(0)),
+            _ => {
+                let usernames = passwords
+                    .iter()
+                    .map(|password| password.username.clone())
+                    .collect::<Vec<_>>();
+                let index = choose(&usernames)?;
+
+                Ok(passwords.remove(index))
+            }
         }
     }
 
+    pub fn set_folder(&mut self, folder: &str) {
+        self.folder = folder
+            .split('/')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+    }
+
+    /// Tags are trimmed, sorted and deduplicated. They cannot contain `TAG_SEPARATOR`, which
+    /// joins them in dumps and backups.
+    pub fn set_tags<T: AsRef<str>>(&mut self, tags: &[T]) -> Result<(), Error> {
+        let mut tags = tags
+            .iter()
+            .map(|tag| tag.as_ref().trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<_>>();
+
+        if let Some(tag) = tags.iter().find(|tag| tag.contains(TAG_SEPARATOR)) {
+            return Err(Error::BadTag(tag.clone(), TAG_SEPARATOR));
+        }
+
+        tags.sort();
+        tags.dedup();
+
+        self.tags = tags;
+
+        Ok(())
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own_tag| own_tag == tag)
+    }
+
+    /// Whether the password is in `folder` or one of its subfolders.
+    pub fn is_in_folder(&self, folder: &str) -> bool {
+        let folder = folder.trim_matches('/');
+
+        folder.is_empty()
+            || self.folder == folder
+            || self.folder.starts_with(&format!("{}/", folder))
+    }
+
     pub fn is_encrypted(&self) -> bool {
-        self.encrypted == 1
+        self.cipher != CipherVersion::Plaintext
     }
 
-    pub fn decrypt_password(&mut self, key: &str) -> Result<(), Error> {
-        if self.is_encrypted() {
-            self.password = decrypt(&self.password, key)?;
-            self.encrypted = 0;
+    /// One line naming the entry in listings, such as `place (username)`.
+    pub fn summary(&self) -> String {
+        let (_, username_label, _) = self.kind.labels();
+        let mut summary = self.place.clone();
+
+        if username_label.is_some() {
+            summary.push_str(&format!(" ({})", self.username));
+        }
+        if self.kind != EntryKind::Login {
+            summary.push_str(&format!(" [{}]", self.kind));
+        }
+
+        summary
+    }
+
+    /// Whether the one-time password is sealed. It is sealed with the current cipher even when
+    /// the rest of the entry is not encrypted.
+    fn has_sealed_totp(&self) -> bool {
+        !self.totp.is_empty() && !self.has_open_totp()
+    }
+
+    /// Whether the entry holds a one-time password that still has to be sealed.
+    pub fn has_open_totp(&self) -> bool {
+        self.totp.starts_with(TOTP_URI_PREFIX)
+    }
+
+    /// Whether any value needs the key to be read.
+    pub fn is_sealed(&self) -> bool {
+        self.is_encrypted() || self.has_sealed_totp()
+    }
+
+    /// Whether any value is sealed with the current cipher, whose key depends on the vault's salt.
+    pub fn is_sealed_with_salt(&self) -> bool {
+        self.cipher == CipherVersion::CURRENT || self.has_sealed_totp()
+    }
+
+    /// Seals the one-time password, whether the entry is encrypted or not.
+    pub fn seal_totp(&mut self, key: &VaultKey) -> Result<(), Error> {
+        if self.has_open_totp() {
+            self.totp = encrypt(&self.totp, key)?;
         }
 
         Ok(())
     }
 
-    pub fn encrypt_password(&mut self, key: &str) {
+    /// Decrypts the password along with its notes, custom fields and one-time password.
+    pub fn decrypt_password(&mut self, key: &VaultKey) -> Result<(), Error> {
+        if self.has_sealed_totp() {
+            self.totp = decrypt(&self.totp, key)?;
+        }
+
         if !self.is_encrypted() {
-            self.password = encrypt(&self.password, key);
-            self.encrypted = 1;
+            return Ok(());
+        }
+
+        self.password = self.cipher.decrypt(&self.password, key)?;
+
+        if !self.notes.is_empty() {
+            self.notes = self.cipher.decrypt(&self.notes, key)?;
+        }
+
+        for field in self.fields.iter_mut() {
+            field.value = self.cipher.decrypt(&field.value, key)?;
         }
+
+        self.cipher = CipherVersion::Plaintext;
+
+        Ok(())
     }
 
-    pub fn to_csv_row(&self) -> String {
-        format!("{},{},{}\n", self.place, self.username, self.password)
+    /// Encrypts the password along with its notes, custom fields and one-time password.
+    pub fn encrypt_password(&mut self, key: &VaultKey) -> Result<(), Error> {
+        self.seal_totp(key)?;
+
+        if self.is_encrypted() {
+            return Ok(());
+        }
+
+        self.password = encrypt(&self.password, key)?;
+
+        if !self.notes.is_empty() {
+            self.notes = encrypt(&self.notes, key)?;
+        }
+
+        for field in self.fields.iter_mut() {
+            field.value = encrypt(&field.value, key)?;
+        }
+
+        self.cipher = CipherVersion::CURRENT;
+
+        Ok(())
+    }
+
+    /// Re-seals every sealed value under `new_key`. Entries that are not encrypted stay so, only
+    /// their one-time password is re-sealed.
+    pub fn reseal(&mut self, old_key: &VaultKey, new_key: &VaultKey) -> Result<(), Error> {
+        let was_encrypted = self.is_encrypted();
+
+        self.decrypt_password(old_key)?;
+
+        if was_encrypted {
+            self.encrypt_password(new_key)
+        } else {
+            self.seal_totp(new_key)
+        }
+    }
+
+    /// Adds the field, replacing any existing field with the same name.
+    pub fn set_field(&mut self, field: CustomField) {
+        self.remove_field(&field.name);
+        self.fields.push(field);
+        self.fields.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    pub fn remove_field(&mut self, name: &str) {
+        self.fields.retain(|field| field.name != name);
     }
 
-    pub fn dump(&self) -> String {
-        format!(
-            "{},{},{},{}",
-            self.place, self.username, self.password, self.encrypted
-        )
+    /// CSV cells for the custom fields, in the order of `headers` (see `field_headers`).
+    pub fn field_cells(&self, headers: &[String]) -> Vec<String> {
+        headers
+            .iter()
+            .map(|header| {
+                self.fields
+                    .iter()
+                    .find(|field| &field.header() == header)
+                    .map(|field| field.value.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
     }
+
+    /// Timestamp `sort_by` refers to. Never-used passwords sort before every used one.
+    pub fn timestamp(&self, sort_by: SortBy) -> Option<i64> {
+        match sort_by {
+            SortBy::Place => None,
+            SortBy::Created => Some(self.created_at),
+            SortBy::Updated => Some(self.updated_at),
+            SortBy::LastUsed => self.last_used_at,
+        }
+    }
+
+    pub fn dump(&self, kdf_salt: &str, field_headers: &[String]) -> String {
+        let mut cells = vec![
+            self.place.clone(),
+            self.username.clone(),
+            self.password.clone(),
+            self.cipher.to_string(),
+            (self.kind as i32).to_string(),
+            self.folder.clone(),
+            self.tags.join(TAG_SEPARATOR),
+            self.notes.clone(),
+            self.totp.clone(),
+            kdf_salt.to_string(),
+        ];
+        cells.extend(self.field_cells(field_headers));
+
+        csv::write_record(&cells)
+    }
+}
+
+impl CustomField {
+    /// CSV column name of the field: `field:<name>`, or `concealed:<name>` for concealed fields.
+    pub fn header(&self) -> String {
+        if self.concealed {
+            format!("{}{}", CSV_CONCEALED_FIELD_PREFIX, self.name)
+        } else {
+            format!("{}{}", CSV_FIELD_PREFIX, self.name)
+        }
+    }
+
+    /// Reverse of `header`, with an empty value.
+    pub fn from_header(header: &str) -> Option<Self> {
+        if let Some(name) = header.strip_prefix(CSV_CONCEALED_FIELD_PREFIX) {
+            Some(Self {
+                name: name.to_string(),
+                value: String::new(),
+                concealed: true,
+            })
+        } else {
+            header.strip_prefix(CSV_FIELD_PREFIX).map(|name| Self {
+                name: name.to_string(),
+                value: String::new(),
+                concealed: false,
+            })
+        }
+    }
+}
+
+/// Every custom field column needed to write `passwords` to a CSV file.
+pub fn field_headers(passwords: &[Password]) -> Vec<String> {
+    let mut headers = passwords
+        .iter()
+        .flat_map(|password| password.fields.iter().map(CustomField::header))
+        .collect::<Vec<_>>();
+
+    headers.sort();
+    headers.dedup();
+
+    headers
 }
@@ -0,0 +1,172 @@
+use base32::Alphabet;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use url::Url;
+
+use crate::consts::{TOTP_DEFAULT_DIGITS, TOTP_DEFAULT_PERIOD};
+use crate::errors::Error;
+
+const SECRET_ALPHABET: Alphabet = Alphabet::Rfc4648 { padding: false };
+
+/// Hash function used to sign the time counter.
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+/// Time-based one-time password generator (RFC 6238).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Totp {
+    pub secret: Vec<u8>,
+    pub algorithm: TotpAlgorithm,
+    pub digits: u32,
+    pub period: u64,
+    pub label: String,
+}
+
+impl TotpAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Sha1 => "SHA1",
+            Self::Sha256 => "SHA256",
+            Self::Sha512 => "SHA512",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, Error> {
+        match name.to_uppercase().as_str() {
+            "SHA1" => Ok(Self::Sha1),
+            "SHA256" => Ok(Self::Sha256),
+            "SHA512" => Ok(Self::Sha512),
+            _ => Err(Error::BadTotp(format!("unsupported algorithm `{}`", name))),
+        }
+    }
+}
+
+impl Totp {
+    /// Creates a generator from a base32 secret, as shown by most sites next to their QR code.
+    pub fn new(
+        secret: &str,
+        algorithm: TotpAlgorithm,
+        digits: u32,
+        period: u64,
+        label: String,
+    ) -> Result<Self, Error> {
+        let secret = secret
+            .chars()
+            .filter(|char| !char.is_whitespace() && *char != '=')
+            .collect::<String>()
+            .to_uppercase();
+        let secret = base32::decode(SECRET_ALPHABET, &secret)
+            .filter(|secret| !secret.is_empty())
+            .ok_or(Error::BadTotp("the secret is not valid base32".to_string()))?;
+
+        if !(6..=8).contains(&digits) {
+            return Err(Error::BadTotp("codes must have 6 to 8 digits".to_string()));
+        }
+        if period == 0 {
+            return Err(Error::BadTotp(
+                "the period must be at least a second".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            secret,
+            algorithm,
+            digits,
+            period,
+            label,
+        })
+    }
+
+    /// Parses an `otpauth://totp/...` URI. Missing parameters take the RFC 6238 defaults.
+    pub fn from_uri(uri: &str) -> Result<Self, Error> {
+        let url = Url::parse(uri).map_err(|err| Error::BadTotp(err.to_string()))?;
+
+        if url.scheme() != "otpauth" || url.host_str() != Some("totp") {
+            return Err(Error::BadTotp(
+                "expected an otpauth://totp/ URI".to_string(),
+            ));
+        }
+
+        let label = percent_decode(url.path().trim_start_matches('/'));
+        let mut secret = None;
+        let mut algorithm = TotpAlgorithm::Sha1;
+        let mut digits = TOTP_DEFAULT_DIGITS;
+        let mut period = TOTP_DEFAULT_PERIOD;
+
+        for (name, value) in url.query_pairs() {
+            match name.as_ref() {
+                "secret" => secret = Some(value.to_string()),
+                "algorithm" => algorithm = TotpAlgorithm::from_name(&value)?,
+                "digits" => digits = value.parse().map_err(|_| Error::ParsingError)?,
+                "period" => period = value.parse().map_err(|_| Error::ParsingError)?,
+                _ => (),
+            }
+        }
+
+        let secret = secret.ok_or(Error::BadTotp("the URI has no secret".to_string()))?;
+
+        Self::new(&secret, algorithm, digits, period, label)
+    }
+
+    pub fn to_uri(&self) -> Result<String, Error> {
+        let mut url =
+            Url::parse("otpauth://totp/").map_err(|err| Error::BadTotp(err.to_string()))?;
+
+        url.set_path(&self.label);
+        url.query_pairs_mut()
+            .append_pair("secret", &base32::encode(SECRET_ALPHABET, &self.secret))
+            .append_pair("algorithm", self.algorithm.name())
+            .append_pair("digits", &self.digits.to_string())
+            .append_pair("period", &self.period.to_string());
+
+        Ok(url.to_string())
+    }
+
+    /// Code valid at `timestamp` (seconds since the Unix epoch).
+    pub fn code_at(&self, timestamp: u64) -> String {
+        let counter = (timestamp / self.period).to_be_bytes();
+        let hash = match self.algorithm {
+            TotpAlgorithm::Sha1 => sign::<Hmac<Sha1>>(&self.secret, &counter),
+            TotpAlgorithm::Sha256 => sign::<Hmac<Sha256>>(&self.secret, &counter),
+            TotpAlgorithm::Sha512 => sign::<Hmac<Sha512>>(&self.secret, &counter),
+        };
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let truncated = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+        let code = truncated % 10u32.pow(self.digits);
+
+        format!("{:0width$}", code, width = self.digits as usize)
+    }
+
+    /// Seconds the code valid at `timestamp` has left.
+    pub fn remaining_at(&self, timestamp: u64) -> u64 {
+        self.period - timestamp % self.period
+    }
+}
+
+fn sign<M: Mac + KeyInit>(key: &[u8], message: &[u8]) -> Vec<u8> {
+    // HMAC accepts keys of any length, so this cannot fail.
+    let mut mac = <M as KeyInit>::new_from_slice(key).expect("HMAC key of any length");
+
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Decodes a URI path segment. A `+` stays literal, unlike in form encoding.
+fn percent_decode(input: &str) -> String {
+    url::form_urlencoded::parse(format!("label={}", input.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, value)| value.to_string())
+        .unwrap_or_default()
+}
@@ -1,32 +1,61 @@
 use crate::consts::communications::{
-    CONFIRM_KEY, ENTER_KEY, ERROR_CONFIRMING_KEY, WRONG_KEY, YES_NO,
+    CONFIRM_BACKUP_PASSPHRASE, CONFIRM_KEY, ENTER_BACKUP_PASSPHRASE, ENTER_CHOICE, ENTER_KEY,
+    ERROR_CONFIRMING_BACKUP_PASSPHRASE, ERROR_CONFIRMING_KEY, WRONG_KEY, YES_NO,
 };
-use crate::consts::{BACKUP_FILE_NAME, CSV_PASSWORD, CSV_PLACE, CSV_USERNAME};
+use crate::consts::{NONCE_LENGTH, SALT_LENGTH};
 use crate::database::objects::{ConfigItem, ConfigParams};
 use crate::database::queries::DatabaseInterface;
+use crate::database::utils::get_kdf_salt;
 use crate::errors::Error;
-use crate::password::Password;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use base64::prelude::{Engine, BASE64_STANDARD};
 use bcrypt::verify;
+use chrono::{DateTime, Local};
 use magic_crypt::{new_magic_crypt, MagicCryptTrait};
+use rand::RngCore;
 use rpassword::prompt_password;
 use std::fs;
 use std::io::{stdin, Write};
-use std::path::PathBuf;
+use std::path::Path;
 
-pub async fn save_key(conn: &mut DatabaseInterface) -> Result<(), Error> {
-    let key: String;
+pub fn ask_new_key(question: &str) -> Result<String, Error> {
+    ask_confirmed_secret(question, CONFIRM_KEY, ERROR_CONFIRMING_KEY)
+}
 
+pub fn ask_new_backup_passphrase() -> Result<String, Error> {
+    ask_confirmed_secret(
+        ENTER_BACKUP_PASSPHRASE,
+        CONFIRM_BACKUP_PASSPHRASE,
+        ERROR_CONFIRMING_BACKUP_PASSPHRASE,
+    )
+}
+
+/// Asks for a secret twice, until both answers match.
+fn ask_confirmed_secret(
+    question: &str,
+    confirmation: &str,
+    mismatch: &str,
+) -> Result<String, Error> {
     loop {
-        let entered_key = prompt_password(ENTER_KEY).map_err(|_| Error::ReadError)?;
-        let confirmation_key = prompt_password(CONFIRM_KEY).map_err(|_| Error::ReadError)?;
+        let entered_key = prompt_password(question).map_err(|_| Error::ReadError)?;
+        let confirmation_key = prompt_password(confirmation).map_err(|_| Error::ReadError)?;
+
+        if entered_key.is_empty() {
+            return Err(Error::EmptyInput);
+        }
 
         if entered_key == confirmation_key {
-            key = entered_key;
-            break;
+            return Ok(entered_key);
         } else {
-            println!("{}", ERROR_CONFIRMING_KEY);
+            println!("{}", mismatch);
         }
     }
+}
+
+pub async fn save_key(conn: &mut DatabaseInterface) -> Result<(), Error> {
+    let key = ask_new_key(ENTER_KEY)?;
 
     let config = ConfigItem {
         name: ConfigParams::AccessCheck,
@@ -38,7 +67,36 @@ pub async fn save_key(conn: &mut DatabaseInterface) -> Result<(), Error> {
     Ok(())
 }
 
-pub async fn ask_valid_key(conn: &mut DatabaseInterface) -> Result<String, Error> {
+/// Key material unlocked by the user's master key.
+///
+/// Entries are sealed with AES-256-GCM under a key derived with Argon2id and the vault's salt.
+/// The master key itself is kept around so entries written by older versions can still be read.
+pub struct VaultKey {
+    master_key: String,
+    cipher: Aes256Gcm,
+}
+
+impl VaultKey {
+    pub fn derive(master_key: &str, salt: &[u8]) -> Result<Self, Error> {
+        let mut derived_key = [0u8; 32];
+
+        Argon2::default()
+            .hash_password_into(master_key.as_bytes(), salt, &mut derived_key)
+            .map_err(|err| Error::KeyDerivationError(err.to_string()))?;
+
+        Ok(Self {
+            master_key: master_key.to_string(),
+            cipher: Aes256Gcm::new(&derived_key.into()),
+        })
+    }
+
+    /// Derives the key the same master key gives with another vault's salt.
+    pub fn with_salt(&self, salt: &[u8]) -> Result<Self, Error> {
+        Self::derive(&self.master_key, salt)
+    }
+}
+
+pub async fn ask_valid_key(conn: &mut DatabaseInterface) -> Result<VaultKey, Error> {
     let setting = conn.get_setting(ConfigParams::AccessCheck).await?;
 
     loop {
@@ -46,35 +104,65 @@ pub async fn ask_valid_key(conn: &mut DatabaseInterface) -> Result<String, Error
         let verification = verify(&key, &setting.value).map_err(|_| Error::VerificationError)?;
 
         if verification {
-            return Ok(key);
+            let salt = get_kdf_salt(conn).await?;
+
+            return VaultKey::derive(&key, &salt);
         } else {
             println!("{}", WRONG_KEY);
         }
     }
 }
 
-pub fn encrypt(plaintext: &str, key: &str) -> String {
-    let mc = new_magic_crypt!(key, 256);
+pub fn generate_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LENGTH];
+
+    rand::thread_rng().fill_bytes(&mut salt);
 
-    mc.encrypt_str_to_base64(plaintext)
+    salt
 }
 
-pub fn decrypt(ciphertext: &str, key: &str) -> Result<String, Error> {
-    let mc = new_magic_crypt!(key, 256);
+pub fn encrypt(plaintext: &str, key: &VaultKey) -> Result<String, Error> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = key
+        .cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| Error::EncryptionError)?;
 
-    mc.decrypt_base64_to_string(ciphertext)
-        .map_err(|err| Error::BadDecryption(err))
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+
+    Ok(BASE64_STANDARD.encode(sealed))
 }
 
-pub fn create_backup(location: &PathBuf, passwords: &Vec<Password>) -> Result<(), Error> {
-    let mut result_string = format!("{},{},{}\n", CSV_PLACE, CSV_USERNAME, CSV_PASSWORD);
+pub fn decrypt(ciphertext: &str, key: &VaultKey) -> Result<String, Error> {
+    let sealed = BASE64_STANDARD
+        .decode(ciphertext)
+        .map_err(|_| Error::MalformedCiphertext)?;
 
-    for password in passwords {
-        result_string.push_str(&password.to_csv_row());
+    if sealed.len() < NONCE_LENGTH {
+        return Err(Error::MalformedCiphertext);
     }
 
-    let mut file = fs::File::create(location.join(BACKUP_FILE_NAME)).map_err(|_| Error::BadDump)?;
-    file.write_all(result_string.as_bytes())
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+    let plaintext = key
+        .cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::TamperedCiphertext)?;
+
+    String::from_utf8(plaintext).map_err(|_| Error::MalformedCiphertext)
+}
+
+/// Decrypts entries sealed by versions before 0.5, which used magic-crypt directly on the master key.
+pub fn decrypt_legacy(ciphertext: &str, key: &VaultKey) -> Result<String, Error> {
+    let mc = new_magic_crypt!(&key.master_key, 256);
+
+    mc.decrypt_base64_to_string(ciphertext)
+        .map_err(Error::BadDecryption)
+}
+
+pub fn create_backup(location: &Path, file_name: &str, contents: &str) -> Result<(), Error> {
+    let mut file = fs::File::create(location.join(file_name)).map_err(|_| Error::BadDump)?;
+    file.write_all(contents.as_bytes())
         .map_err(|_| Error::BadDump)?;
 
     Ok(())
@@ -118,7 +206,7 @@ pub fn find_clomun_index<'a, T: Iterator<Item = &'a str> + Clone>(
         }
 
         let unwrapped = user_defined_header.unwrap();
-        let user_defined_index = headers.position(|p| p == &unwrapped);
+        let user_defined_index = headers.position(|p| p == unwrapped);
 
         if user_defined_index.is_none() {
             return Err(Error::NoHeader(unwrapped));
@@ -130,6 +218,29 @@ pub fn find_clomun_index<'a, T: Iterator<Item = &'a str> + Clone>(
     Ok(default_index.unwrap())
 }
 
+/// Reads lines until an empty one, for answers that span several lines.
+pub fn ask_lines(question: &str) -> Result<Option<String>, Error> {
+    let mut lines = Vec::new();
+
+    println!("\n{}", question);
+
+    for line in stdin().lines() {
+        let line = line.map_err(|_| Error::ReadError)?;
+
+        if line.trim().is_empty() {
+            break;
+        }
+
+        lines.push(line.trim_end().to_string());
+    }
+
+    if lines.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(lines.join("\n")))
+    }
+}
+
 pub fn ask_bool(question: &str) -> Result<bool, Error> {
     let confirmation = ask_question(&format!("{} {}: ", question, YES_NO))?;
 
@@ -140,3 +251,54 @@ pub fn ask_bool(question: &str) -> Result<bool, Error> {
         None => Err(Error::EmptyInput),
     }
 }
+
+pub fn ask_choice(question: &str, options: &[String]) -> Result<usize, Error> {
+    println!("\n{}", question);
+
+    for (index, option) in options.iter().enumerate() {
+        println!("{}: {}", index, option);
+    }
+
+    let answer = ask_question(ENTER_CHOICE)?.ok_or(Error::EmptyInput)?;
+    let index = answer.parse::<usize>().map_err(|_| Error::ParsingError)?;
+
+    if index < options.len() {
+        Ok(index)
+    } else {
+        Err(Error::BadInput(answer))
+    }
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(time) => time
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Parses positive durations such as `30d`, `12h` or `2w` into seconds.
+pub fn parse_duration(input: &str) -> Result<i64, Error> {
+    let unit_start = input.char_indices().last().map_or(0, |(index, _)| index);
+    let (amount, unit) = input.split_at(unit_start);
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(Error::BadInput(input.to_string())),
+    };
+
+    let amount = amount
+        .parse::<i64>()
+        .map_err(|_| Error::BadInput(input.to_string()))?;
+
+    if amount <= 0 {
+        return Err(Error::BadInput(input.to_string()));
+    }
+
+    amount.checked_mul(seconds).ok_or(Error::ParsingError)
+}
@@ -0,0 +1,190 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::Aes256Gcm;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use password_manager::backup::{open, seal, BackupHeader};
+use password_manager::consts::{
+    BACKUP_MAX_MEMORY_COST, BACKUP_MAX_PARALLELISM, BACKUP_MAX_TIME_COST,
+};
+use password_manager::errors::Error;
+use password_manager::password::{CustomField, EntryKind, Password};
+
+fn passwords() -> Vec<Password> {
+    let mut mail = Password::new("me".to_string(), "mail".to_string(), "hunter2".to_string());
+    mail.set_folder("personal");
+    mail.set_tags(&["email", "2fa"]).unwrap();
+    mail.notes = "recovery, \"codes\"\nin the drawer".to_string();
+    mail.totp = "otpauth://totp/mail:me?secret=JBSWY3DPEHPK3PXP".to_string();
+    mail.set_field(CustomField {
+        name: "pin".to_string(),
+        value: "1234".to_string(),
+        concealed: true,
+    });
+
+    let mut card = Password::new(
+        "Jane Doe".to_string(),
+        "visa".to_string(),
+        "4111".to_string(),
+    );
+    card.kind = EntryKind::Card;
+
+    vec![mail, card]
+}
+
+/// Seals `passwords` the way `seal` does, under a header claiming `entries` entries.
+fn seal_claiming(entries: usize, passwords: &[Password], passphrase: &str) -> String {
+    let header = BackupHeader::new(entries);
+    let header_text = header.to_string();
+    let params = Params::new(
+        header.memory_cost,
+        header.time_cost,
+        header.parallelism,
+        Some(32),
+    )
+    .unwrap();
+    let mut key = [0u8; 32];
+
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &header.salt, &mut key)
+        .unwrap();
+
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let contents = password_manager::backup::to_csv(passwords);
+    let payload = Payload {
+        msg: contents.as_bytes(),
+        aad: header_text.as_bytes(),
+    };
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        Aes256Gcm::new(&key.into())
+            .encrypt(&nonce, payload)
+            .unwrap(),
+    );
+
+    format!("{}\n\n{}\n", header_text, BASE64_STANDARD.encode(sealed))
+}
+
+#[test]
+fn opens_what_it_seals() {
+    let archive = seal(&passwords(), "correct horse").unwrap();
+
+    assert!(!archive.contains("hunter2"));
+
+    let opened = open(&archive, "correct horse").unwrap();
+
+    assert_eq!(opened.len(), 2);
+
+    for (opened, original) in opened.iter().zip(passwords().iter()) {
+        assert_eq!(opened.place, original.place);
+        assert_eq!(opened.username, original.username);
+        assert_eq!(opened.password, original.password);
+        assert_eq!(opened.kind, original.kind);
+        assert_eq!(opened.folder, original.folder);
+        assert_eq!(opened.tags, original.tags);
+        assert_eq!(opened.notes, original.notes);
+        assert_eq!(opened.totp, original.totp);
+        assert_eq!(opened.fields, original.fields);
+    }
+}
+
+#[test]
+fn rejects_a_wrong_passphrase() {
+    let archive = seal(&passwords(), "correct horse").unwrap();
+
+    assert!(matches!(
+        open(&archive, "battery staple"),
+        Err(Error::WrongPassphrase)
+    ));
+}
+
+#[test]
+fn rejects_a_tampered_header() {
+    let archive = seal(&passwords(), "correct horse").unwrap();
+    let tampered = archive.replace("entries = 2", "entries = 1");
+
+    assert_ne!(tampered, archive);
+    assert!(matches!(
+        open(&tampered, "correct horse"),
+        Err(Error::WrongPassphrase)
+    ));
+}
+
+#[test]
+fn rejects_tampered_entries() {
+    let archive = seal(&passwords(), "correct horse").unwrap();
+    let (header, sealed) = archive.split_once("\n\n").unwrap();
+    let mut sealed = BASE64_STANDARD.decode(sealed.trim()).unwrap();
+    let last = sealed.len() - 1;
+    sealed[last] ^= 1;
+    let tampered = format!("{}\n\n{}\n", header, BASE64_STANDARD.encode(sealed));
+
+    assert!(matches!(
+        open(&tampered, "correct horse"),
+        Err(Error::WrongPassphrase)
+    ));
+}
+
+#[test]
+fn rejects_an_entry_count_mismatch() {
+    let archive = seal_claiming(3, &passwords(), "correct horse");
+
+    assert!(matches!(
+        open(&archive, "correct horse"),
+        Err(Error::BadBackup(message)) if message == "expected 3 entries, found 2"
+    ));
+    assert_eq!(
+        open(
+            &seal_claiming(2, &passwords(), "correct horse"),
+            "correct horse"
+        )
+        .unwrap()
+        .len(),
+        2
+    );
+}
+
+/// Replaces the value of `name` in the header of a sealed archive.
+fn with_header_value(archive: &str, name: &str, value: &str) -> String {
+    let (header, sealed) = archive.split_once("\n\n").unwrap();
+    let header = header
+        .lines()
+        .map(|line| match line.split_once(" = ") {
+            Some((key, _)) if key == name => format!("{} = {}", key, value),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("{}\n\n{}", header, sealed)
+}
+
+#[test]
+fn rejects_header_numbers_out_of_range() {
+    let archive = seal(&passwords(), "correct horse").unwrap();
+
+    // 2^32 + 19456 would pass for 19456 once truncated to 32 bits.
+    let overflowing = with_header_value(&archive, "memory_cost", "4294986752");
+
+    assert!(matches!(
+        open(&overflowing, "correct horse"),
+        Err(Error::BadBackup(message)) if message == "`memory_cost` is out of range"
+    ));
+}
+
+#[test]
+fn rejects_key_derivation_costs_above_the_maximum() {
+    let archive = seal(&passwords(), "correct horse").unwrap();
+
+    for (name, value) in [
+        ("memory_cost", BACKUP_MAX_MEMORY_COST + 1),
+        ("time_cost", BACKUP_MAX_TIME_COST + 1),
+        ("parallelism", BACKUP_MAX_PARALLELISM + 1),
+    ] {
+        let costly = with_header_value(&archive, name, &value.to_string());
+
+        assert!(matches!(
+            open(&costly, "correct horse"),
+            Err(Error::BadBackup(message)) if message.starts_with(&format!("`{}` is above", name))
+        ));
+    }
+}
@@ -0,0 +1,23 @@
+//! Helpers shared by the integration tests. Each test file uses only some of them.
+#![allow(dead_code)]
+
+use password_manager::database::migrations::migrate;
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::password::Password;
+use sqlx::{Connection, SqliteConnection};
+
+/// Empty vault at the latest schema, kept in memory.
+pub async fn vault() -> DatabaseInterface {
+    let connection = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    let mut conn = DatabaseInterface::from(connection);
+
+    conn.create_config_table().await.unwrap();
+    conn.create_password_table().await.unwrap();
+    migrate(&mut conn, false).await.unwrap();
+
+    conn
+}
+
+pub fn password(place: &str, username: &str, secret: &str) -> Password {
+    Password::new(username.to_string(), place.to_string(), secret.to_string())
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::{password, vault};
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::operations::{apply_import, plan_import, ImportAction, OnConflict};
+use password_manager::password::{CipherVersion, Password};
+use password_manager::utils::{generate_salt, VaultKey};
+
+/// Vault holding `mail` for `me`, and `shop` for `me` in the trash.
+async fn stocked_vault() -> DatabaseInterface {
+    let mut conn = vault().await;
+
+    conn.insert_password(&password("mail", "me", "stored"))
+        .await
+        .unwrap();
+    conn.insert_password(&password("shop", "me", "trashed"))
+        .await
+        .unwrap();
+
+    let shop = conn.get_password("shop").await.unwrap().remove(0);
+    conn.trash_password(shop.id).await.unwrap();
+
+    conn
+}
+
+/// Rows of a dump: one clashing with a stored entry, one with a trashed entry, and a new one.
+fn dump() -> Vec<(Password, Option<String>)> {
+    vec![
+        (password("mail", "me", "dumped"), None),
+        (password("shop", "me", "dumped"), None),
+        (password("bank", "me", "dumped"), None),
+    ]
+}
+
+/// Place, username, password and whether the entry is trashed, sorted by place.
+async fn contents(conn: &mut DatabaseInterface) -> Vec<(String, String, String, bool)> {
+    let mut passwords = conn.get_all_passwords().await.unwrap();
+    passwords.sort_by(|a, b| a.place.cmp(&b.place));
+
+    passwords
+        .into_iter()
+        .map(|password| {
+            (
+                password.place,
+                password.username,
+                password.password,
+                password.deleted_at.is_some(),
+            )
+        })
+        .collect()
+}
+
+fn row(place: &str, secret: &str, trashed: bool) -> (String, String, String, bool) {
+    (
+        place.to_string(),
+        "me".to_string(),
+        secret.to_string(),
+        trashed,
+    )
+}
+
+fn key() -> VaultKey {
+    VaultKey::derive("master", &generate_salt()).unwrap()
+}
+
+async fn import(conn: &mut DatabaseInterface, on_conflict: OnConflict) {
+    let entries = plan_import(conn, dump(), on_conflict).await.unwrap();
+
+    apply_import(conn, &entries, &key()).await.unwrap();
+}
+
+#[async_std::test]
+async fn skips_conflicting_rows() {
+    let mut conn = stocked_vault().await;
+
+    import(&mut conn, OnConflict::Skip).await;
+
+    assert_eq!(
+        contents(&mut conn).await,
+        [
+            row("bank", "dumped", false),
+            row("mail", "stored", false),
+            row("shop", "trashed", true),
+        ]
+    );
+}
+
+#[async_std::test]
+async fn overwrites_conflicting_entries() {
+    let mut conn = stocked_vault().await;
+    let key = key();
+    let entries = plan_import(&mut conn, dump(), OnConflict::Overwrite)
+        .await
+        .unwrap();
+
+    apply_import(&mut conn, &entries, &key).await.unwrap();
+
+    assert_eq!(
+        contents(&mut conn).await,
+        [
+            row("bank", "dumped", false),
+            row("mail", "dumped", false),
+            row("shop", "dumped", false),
+        ]
+    );
+
+    let mail = conn.get_password("mail").await.unwrap().remove(0);
+    let mut history = conn.get_history(mail.id).await.unwrap();
+
+    assert_eq!(history.len(), 1);
+
+    // The overwritten password was stored in plaintext, but its history is sealed all the same.
+    let previous = &mut history[0].password;
+    assert_eq!(previous.cipher, CipherVersion::CURRENT);
+    previous.decrypt_password(&key).unwrap();
+    assert_eq!(previous.password, "stored");
+}
+
+#[async_std::test]
+async fn renames_conflicting_rows() {
+    let mut conn = stocked_vault().await;
+
+    import(&mut conn, OnConflict::Rename).await;
+
+    assert_eq!(
+        contents(&mut conn).await,
+        [
+            row("bank", "dumped", false),
+            row("mail", "stored", false),
+            row("mail (2)", "dumped", false),
+            row("shop", "trashed", true),
+            row("shop (2)", "dumped", false),
+        ]
+    );
+}
+
+#[async_std::test]
+async fn resolves_conflicts_between_rows_of_the_dump() {
+    let rows = vec![
+        (password("bank", "me", "first"), None),
+        (password("bank", "me", "second"), None),
+    ];
+
+    let mut conn = vault().await;
+    let entries = plan_import(&mut conn, rows.clone(), OnConflict::Overwrite)
+        .await
+        .unwrap();
+    apply_import(&mut conn, &entries, &key()).await.unwrap();
+
+    assert!(matches!(entries[0].action, ImportAction::Skip));
+    assert_eq!(contents(&mut conn).await, [row("bank", "second", false)]);
+
+    let mut conn = vault().await;
+    let entries = plan_import(&mut conn, rows, OnConflict::Rename)
+        .await
+        .unwrap();
+    apply_import(&mut conn, &entries, &key()).await.unwrap();
+
+    assert_eq!(entries[1].renamed_from.as_deref(), Some("bank"));
+    assert_eq!(
+        contents(&mut conn).await,
+        [
+            row("bank", "first", false),
+            row("bank (2)", "second", false)
+        ]
+    );
+}
+
+/// `--dry-run` prints the plan and stops before applying it.
+#[async_std::test]
+async fn a_dry_run_writes_nothing() {
+    for on_conflict in [OnConflict::Skip, OnConflict::Overwrite, OnConflict::Rename] {
+        let mut conn = stocked_vault().await;
+        let before = contents(&mut conn).await;
+
+        let entries = plan_import(&mut conn, dump(), on_conflict).await.unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(contents(&mut conn).await, before);
+
+        let mail = conn.get_password("mail").await.unwrap().remove(0);
+        assert!(conn.get_history(mail.id).await.unwrap().is_empty());
+    }
+}
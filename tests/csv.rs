@@ -0,0 +1,99 @@
+use password_manager::backup;
+use password_manager::csv::{parse, write_record};
+use password_manager::errors::Error;
+use password_manager::password::Password;
+use proptest::prelude::*;
+
+fn write(records: &[Vec<String>]) -> String {
+    records.iter().map(|record| write_record(record)).collect()
+}
+
+fn fields(input: &str) -> Vec<Vec<String>> {
+    parse(input)
+        .unwrap()
+        .into_iter()
+        .map(|record| record.fields)
+        .collect()
+}
+
+fn malformed(input: &str) -> (usize, usize) {
+    match parse(input) {
+        Err(Error::MalformedCsv(line, column, _)) => (line, column),
+        other => panic!(
+            "expected a malformed CSV error, got {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
+
+proptest! {
+    #[test]
+    fn records_round_trip(
+        records in (1..6usize).prop_flat_map(|width| {
+            prop::collection::vec(prop::collection::vec(any::<String>(), width), 1..8)
+        })
+    ) {
+        prop_assert_eq!(fields(&write(&records)), records);
+    }
+
+    #[test]
+    fn awkward_fields_round_trip(
+        records in (1..6usize).prop_flat_map(|width| {
+            prop::collection::vec(prop::collection::vec("[a\",\r\n ]*", width), 1..8)
+        })
+    ) {
+        prop_assert_eq!(fields(&write(&records)), records);
+    }
+
+    #[test]
+    fn backups_round_trip(
+        entries in prop::collection::vec(
+            (any::<String>(), any::<String>(), r#"[!-~ ]*"#, any::<String>()),
+            0..8,
+        )
+    ) {
+        let passwords = entries
+            .iter()
+            .map(|(place, username, password, notes)| {
+                let mut password = Password::new(username.clone(), place.clone(), password.clone());
+                password.notes = notes.clone();
+                password
+            })
+            .collect::<Vec<_>>();
+
+        let restored = backup::from_csv(&backup::to_csv(&passwords)).unwrap();
+
+        prop_assert_eq!(restored.len(), entries.len());
+
+        for (password, (place, username, secret, notes)) in restored.iter().zip(entries.iter()) {
+            prop_assert_eq!(&password.place, place);
+            prop_assert_eq!(&password.username, username);
+            prop_assert_eq!(&password.password, secret);
+            prop_assert_eq!(&password.notes, notes);
+        }
+    }
+}
+
+#[test]
+fn skips_blank_lines() {
+    assert_eq!(fields("a\r\n\r\n\"\"\r\n\n"), vec![vec!["a"], vec![""]]);
+}
+
+#[test]
+fn accepts_bare_line_feeds() {
+    assert_eq!(
+        fields("a,b\n\"c\nd\",e\n"),
+        vec![vec!["a", "b"], vec!["c\nd", "e"]]
+    );
+}
+
+#[test]
+fn reports_where_records_are_malformed() {
+    assert_eq!(malformed("a,b\r\nc,\"d"), (2, 3));
+    assert_eq!(malformed("a,b\r\nc,d\"e\r\n"), (2, 4));
+    assert_eq!(malformed("a,b\r\n\"c\"d,e\r\n"), (2, 4));
+    assert_eq!(malformed("a,b\r\n\"c\r\nc\",d,e\r\n"), (3, 6));
+    assert_eq!(malformed("a,b\r\nc\r\n"), (2, 2));
+    assert_eq!(malformed("a,b\r\n\r\n\r\nc\r\n"), (4, 2));
+    assert_eq!(malformed("a,b\rc,d"), (1, 4));
+}
@@ -0,0 +1,179 @@
+mod common;
+
+use common::password;
+use password_manager::dump::{match_entries, Diff, Difference};
+use password_manager::errors::Error;
+use password_manager::password::{CipherVersion, Password};
+
+fn dumped(passwords: Vec<Password>) -> Vec<(Password, Option<String>)> {
+    passwords
+        .into_iter()
+        .map(|password| (password, Some("c2FsdA==".to_string())))
+        .collect()
+}
+
+/// Diff of plaintext entries, which need no decrypting.
+fn diff(stored: Vec<Password>, dump: Vec<Password>) -> Diff {
+    match_entries(stored, dumped(dump))
+        .compare(|_, _, _| Ok(()))
+        .unwrap()
+}
+
+fn places(passwords: &[Password]) -> Vec<&str> {
+    passwords
+        .iter()
+        .map(|password| password.place.as_str())
+        .collect()
+}
+
+#[test]
+fn finds_no_differences_between_identical_entries() {
+    let entries = || {
+        vec![
+            password("mail", "me", "hunter2"),
+            password("mail", "work", "s3cret"),
+        ]
+    };
+
+    assert!(diff(entries(), entries()).is_empty());
+}
+
+#[test]
+fn finds_entries_added_to_the_dump() {
+    let diff = diff(
+        vec![password("mail", "me", "hunter2")],
+        vec![
+            password("mail", "me", "hunter2"),
+            password("bank", "me", "p1n"),
+        ],
+    );
+
+    assert_eq!(places(&diff.only_in_dump), ["bank"]);
+    assert!(diff.only_in_vault.is_empty());
+    assert!(diff.changed.is_empty());
+}
+
+#[test]
+fn finds_entries_missing_from_the_dump() {
+    let diff = diff(
+        vec![
+            password("mail", "me", "hunter2"),
+            password("bank", "me", "p1n"),
+        ],
+        vec![password("mail", "me", "hunter2")],
+    );
+
+    assert!(diff.only_in_dump.is_empty());
+    assert_eq!(places(&diff.only_in_vault), ["bank"]);
+    assert!(diff.changed.is_empty());
+}
+
+#[test]
+fn finds_changed_entries() {
+    let mut sealed = password("shop", "me", "p4ss");
+    sealed.cipher = CipherVersion::CURRENT;
+
+    let diff = diff(
+        vec![
+            password("mail", "me", "hunter2"),
+            password("bank", "me", "p1n"),
+            sealed,
+        ],
+        vec![
+            password("mail", "me", "hunter3"),
+            password("bank", "jane", "p1n"),
+            password("shop", "me", "p4ss"),
+        ],
+    );
+
+    assert!(diff.only_in_dump.is_empty());
+    assert!(diff.only_in_vault.is_empty());
+
+    let changed = diff
+        .changed
+        .iter()
+        .map(|(password, differences)| (password.place.as_str(), differences.as_slice()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        changed,
+        [
+            ("mail", &[Difference::Password][..]),
+            ("shop", &[Difference::Encryption][..]),
+            ("bank", &[Difference::Username("jane".to_string())][..]),
+        ]
+    );
+}
+
+#[test]
+fn ignores_which_cipher_sealed_the_entries() {
+    let mut stored = password("mail", "me", "sealed");
+    stored.cipher = CipherVersion::Aes256Gcm;
+
+    let mut legacy = password("mail", "me", "sealed");
+    legacy.cipher = CipherVersion::MagicCrypt;
+
+    let diff = match_entries(vec![stored], dumped(vec![legacy]))
+        .compare(|stored, dumped, _| {
+            stored.password = "hunter2".to_string();
+            dumped.password = "hunter2".to_string();
+
+            Ok(())
+        })
+        .unwrap();
+
+    assert!(diff.is_empty());
+}
+
+#[test]
+fn prefers_the_entry_with_the_same_username() {
+    let matches = match_entries(
+        vec![
+            password("mail", "me", "hunter2"),
+            password("mail", "work", "s3cret"),
+        ],
+        dumped(vec![
+            password("mail", "jane", "j4ne"),
+            password("mail", "work", "s3cret"),
+        ]),
+    );
+
+    let pairs = matches
+        .pairs
+        .iter()
+        .map(|(stored, dumped, _)| (stored.username.as_str(), dumped.username.as_str()))
+        .collect::<Vec<_>>();
+
+    assert_eq!(pairs, [("work", "work"), ("me", "jane")]);
+}
+
+#[test]
+fn decrypts_each_pair_with_the_salt_of_its_row() {
+    let mut calls = Vec::new();
+    let diff = match_entries(
+        vec![
+            password("mail", "me", "sealed"),
+            password("bank", "me", "sealed"),
+        ],
+        dumped(vec![password("mail", "me", "sealed")]),
+    )
+    .compare(|stored, dumped, kdf_salt| {
+        calls.push(kdf_salt.map(str::to_string));
+        stored.password = "hunter2".to_string();
+        dumped.password = "hunter2".to_string();
+
+        Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(calls, [Some("c2FsdA==".to_string())]);
+    assert!(diff.changed.is_empty());
+
+    let failed = match_entries(
+        vec![password("mail", "me", "sealed")],
+        dumped(vec![password("mail", "me", "sealed")]),
+    )
+    .compare(|_, _, _| Err(Error::TamperedCiphertext));
+
+    assert!(matches!(failed, Err(Error::TamperedCiphertext)));
+}
@@ -0,0 +1,445 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::vault;
+use password_manager::consts::{NUMBERS, SPECIAL_CHARACTERS};
+use password_manager::database::profiles::GeneratorProfiles;
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::errors::Error;
+use password_manager::operations::rotate_password;
+use password_manager::password::{
+    parse_wordlist, GeneratorArgs, PassphraseOptions, Password, PasswordBuildOptions,
+    PasswordBuilder, Pattern,
+};
+use password_manager::utils::{generate_salt, VaultKey};
+
+fn options(length: usize) -> PasswordBuildOptions {
+    PasswordBuildOptions {
+        length,
+        use_special: true,
+        use_numbers: true,
+        use_upper: true,
+        exclude_char: Vec::new(),
+        min_upper: 0,
+        min_digits: 0,
+        min_special: 0,
+    }
+}
+
+#[test]
+fn meets_the_class_minimums() {
+    let options = PasswordBuildOptions {
+        min_upper: 2,
+        min_digits: 3,
+        min_special: 1,
+        exclude_char: vec!['0', 'A'],
+        ..options(8)
+    };
+
+    for _ in 0..200 {
+        let password = PasswordBuilder::generate_password(options.clone()).unwrap();
+
+        assert_eq!(password.chars().count(), 8);
+        assert!(password.chars().filter(char::is_ascii_uppercase).count() >= 2);
+        assert!(password.chars().filter(|c| NUMBERS.contains(*c)).count() >= 3);
+        assert!(password.chars().any(|c| SPECIAL_CHARACTERS.contains(c)));
+        assert!(!password.contains(['0', 'A']));
+    }
+}
+
+#[test]
+fn spreads_the_required_characters() {
+    let options = PasswordBuildOptions {
+        min_digits: 1,
+        ..options(2)
+    };
+    let digit_first = (0..400)
+        .map(|_| PasswordBuilder::generate_password(options.clone()).unwrap())
+        .filter(|password| password.starts_with(|c| NUMBERS.contains(c)))
+        .count();
+
+    // Without the shuffle the required digit would always come first.
+    assert!(digit_first < 350);
+}
+
+#[test]
+fn rejects_minimums_longer_than_the_password() {
+    let options = PasswordBuildOptions {
+        min_upper: 3,
+        min_digits: 3,
+        ..options(5)
+    };
+
+    assert!(matches!(
+        PasswordBuilder::generate_password(options),
+        Err(Error::ImpossiblePassword(_))
+    ));
+}
+
+#[test]
+fn rejects_minimums_of_disabled_classes() {
+    let options = PasswordBuildOptions {
+        use_numbers: false,
+        min_digits: 1,
+        ..options(12)
+    };
+
+    assert!(matches!(
+        PasswordBuilder::generate_password(options),
+        Err(Error::ImpossiblePassword(message)) if message.contains("--min-digits")
+    ));
+}
+
+#[test]
+fn rejects_excluding_every_character() {
+    let options = PasswordBuildOptions {
+        use_special: false,
+        use_numbers: false,
+        use_upper: false,
+        exclude_char: "abcdefghijklmnopqrstuvwxyz".chars().collect(),
+        ..options(12)
+    };
+
+    assert!(matches!(
+        PasswordBuilder::generate_password(options),
+        Err(Error::ImpossiblePassword(_))
+    ));
+}
+
+#[test]
+fn follows_patterns() {
+    let pattern = Pattern::parse(r"Aaaa-9{4}-[a9]{3}\a\{!", &['1']).unwrap();
+
+    for _ in 0..200 {
+        let password = pattern.generate().chars().collect::<Vec<_>>();
+
+        assert_eq!(password.len(), 16);
+        assert!(password[0].is_ascii_uppercase());
+        assert!(password[1..4].iter().all(char::is_ascii_lowercase));
+        assert_eq!(password[4], '-');
+        assert!(password[5..9]
+            .iter()
+            .all(|c| c.is_ascii_digit() && *c != '1'));
+        assert_eq!(password[9], '-');
+        assert!(password[10..13]
+            .iter()
+            .all(|c| c.is_ascii_lowercase() || (c.is_ascii_digit() && *c != '1')));
+        assert_eq!(password[13..15], ['a', '{']);
+        assert!(SPECIAL_CHARACTERS.contains(password[15]));
+    }
+}
+
+#[test]
+fn rejects_malformed_patterns() {
+    for pattern in [
+        "", "a{2", "{3}a", "a{2}{3}", "a{x}", "[a", "[]", "[ab]", "a}", "a\\", "a{2000}",
+    ] {
+        assert!(
+            matches!(Pattern::parse(pattern, &[]), Err(Error::BadPattern(_))),
+            "{} should be rejected",
+            pattern
+        );
+    }
+}
+
+#[test]
+fn profiles_round_trip_through_json() {
+    let options = GeneratorArgs {
+        length: 16,
+        no_special: true,
+        no_uppercase: false,
+        no_numbers: false,
+        exclude: "0O".to_string(),
+        min_upper: 0,
+        min_digits: 2,
+        min_special: 0,
+        pattern: None,
+        passphrase: false,
+        words: 6,
+        separator: "-".to_string(),
+        capitalize: false,
+        append_digit: false,
+        wordlist: None,
+    };
+
+    let saved = serde_json::to_string(&options).unwrap();
+    let loaded: GeneratorArgs = serde_json::from_str(&saved).unwrap();
+
+    assert_eq!(
+        loaded.to_string(),
+        "--length 16 --no-special --min-digits 2 --exclude '0O'"
+    );
+
+    let password = loaded.generator().unwrap().generate().unwrap();
+
+    assert_eq!(password.len(), 16);
+    assert!(password.chars().filter(char::is_ascii_digit).count() >= 2);
+    assert!(!password.contains(['0', 'O']));
+    assert!(!password.contains(|c| SPECIAL_CHARACTERS.contains(c)));
+}
+
+const WORDLIST: &str = "# dice rolls are optional
+11111 alpha
+11112 bravo
+charlie
+
+alpha
+delta
+";
+
+fn passphrase(words: usize, separator: &str, capitalize: bool, append_digit: bool) -> String {
+    PassphraseOptions::new(
+        words,
+        separator.to_string(),
+        capitalize,
+        append_digit,
+        WORDLIST,
+    )
+    .unwrap()
+    .generate()
+}
+
+#[test]
+fn reads_wordlists_without_repeats() {
+    assert_eq!(
+        parse_wordlist(WORDLIST).unwrap(),
+        ["alpha", "bravo", "charlie", "delta"]
+    );
+    assert!(matches!(
+        parse_wordlist("same\n11111 same\n"),
+        Err(Error::BadWordlist(_))
+    ));
+}
+
+#[test]
+fn joins_the_requested_number_of_words() {
+    let words = ["alpha", "bravo", "charlie", "delta"];
+
+    for count in [1, 4, 9] {
+        let passphrase = passphrase(count, " + ", false, false);
+        let parts = passphrase.split(" + ").collect::<Vec<_>>();
+
+        assert_eq!(parts.len(), count);
+        assert!(parts.iter().all(|part| words.contains(part)));
+    }
+}
+
+#[test]
+fn capitalizes_every_word() {
+    for _ in 0..20 {
+        let passphrase = passphrase(5, "-", true, false);
+
+        assert!(passphrase.split('-').all(|word| {
+            word.starts_with(|c: char| c.is_ascii_uppercase())
+                && word[1..].chars().all(|c| c.is_ascii_lowercase())
+        }));
+    }
+}
+
+#[test]
+fn appends_a_digit_to_the_last_word() {
+    for _ in 0..20 {
+        let passphrase = passphrase(3, "-", false, true);
+        let parts = passphrase.split('-').collect::<Vec<_>>();
+        let (last, rest) = parts.split_last().unwrap();
+
+        assert!(last.ends_with(|c| NUMBERS.contains(c)));
+        assert!(!last[..last.len() - 1].contains(|c| NUMBERS.contains(c)));
+        assert!(!rest.concat().contains(|c| NUMBERS.contains(c)));
+    }
+}
+
+#[test]
+fn rejects_passphrases_without_words() {
+    assert!(matches!(
+        PassphraseOptions::new(0, "-".to_string(), false, false, WORDLIST),
+        Err(Error::ImpossiblePassword(_))
+    ));
+}
+
+/// A `pin` profile of six digits, attached to `bank`.
+fn profiles() -> GeneratorProfiles {
+    let pin = GeneratorArgs {
+        pattern: Some("999999".to_string()),
+        ..GeneratorArgs::default()
+    };
+
+    GeneratorProfiles {
+        profiles: BTreeMap::from([("pin".to_string(), pin)]),
+        policies: BTreeMap::from([("bank".to_string(), "pin".to_string())]),
+    }
+}
+
+fn is_pin(password: &str) -> bool {
+    password.len() == 6 && password.chars().all(|c| c.is_ascii_digit())
+}
+
+#[test]
+fn generates_passwords_of_a_place_with_its_policy() {
+    let profiles = profiles();
+    let defaults = GeneratorArgs::default();
+
+    for profile in [None, Some("pin")] {
+        let generator = profiles.pick(Some("bank"), profile, &defaults).unwrap();
+
+        assert!(is_pin(&generator.generate().unwrap()));
+    }
+
+    let generator = profiles.pick(Some("mail"), None, &defaults).unwrap();
+    assert_eq!(generator.generate().unwrap().len(), defaults.length);
+
+    let generator = profiles.pick(None, Some("pin"), &defaults).unwrap();
+    assert!(is_pin(&generator.generate().unwrap()));
+}
+
+#[test]
+fn refuses_options_that_override_a_policy() {
+    let mut profiles = profiles();
+    profiles
+        .profiles
+        .insert("long".to_string(), GeneratorArgs::default());
+
+    let longer = GeneratorArgs {
+        length: 30,
+        ..GeneratorArgs::default()
+    };
+
+    assert!(matches!(
+        profiles.pick(Some("bank"), None, &longer),
+        Err(Error::PolicyConflict(place, profile)) if place == "bank" && profile == "pin"
+    ));
+    assert!(matches!(
+        profiles.pick(Some("bank"), Some("long"), &GeneratorArgs::default()),
+        Err(Error::PolicyConflict(_, _))
+    ));
+    assert_eq!(
+        profiles
+            .pick(Some("mail"), None, &longer)
+            .unwrap()
+            .generate()
+            .unwrap()
+            .len(),
+        30
+    );
+}
+
+async fn policies(conn: &mut DatabaseInterface) -> Vec<(String, String)> {
+    GeneratorProfiles::load(conn)
+        .await
+        .unwrap()
+        .policies
+        .into_iter()
+        .collect()
+}
+
+fn policy(place: &str) -> (String, String) {
+    (place.to_string(), "pin".to_string())
+}
+
+#[async_std::test]
+async fn policies_follow_renamed_and_purged_places() {
+    let mut conn = vault().await;
+
+    for (place, username) in [("bank", "me"), ("bank", "joint"), ("card", "me")] {
+        conn.insert_password(&Password::new(
+            username.to_string(),
+            place.to_string(),
+            "1234".to_string(),
+        ))
+        .await
+        .unwrap();
+    }
+
+    let mut profiles = profiles();
+    profiles
+        .policies
+        .insert("card".to_string(), "pin".to_string());
+    profiles.save(&mut conn).await.unwrap();
+
+    // Another account is still at `bank`, so both places follow the policy.
+    conn.execute("UPDATE passwords SET place = 'savings' WHERE username = 'joint';")
+        .await
+        .unwrap();
+    GeneratorProfiles::follow_rename(&mut conn, "bank", "savings")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        policies(&mut conn).await,
+        [policy("bank"), policy("card"), policy("savings")]
+    );
+
+    conn.execute("UPDATE passwords SET place = 'checking' WHERE place = 'bank';")
+        .await
+        .unwrap();
+    GeneratorProfiles::follow_rename(&mut conn, "bank", "checking")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        policies(&mut conn).await,
+        [policy("card"), policy("checking"), policy("savings")]
+    );
+
+    // Trashed entries keep their policy until they are purged.
+    let card = conn.get_password("card").await.unwrap().remove(0);
+    conn.trash_password(card.id).await.unwrap();
+    GeneratorProfiles::forget_missing_places(&mut conn)
+        .await
+        .unwrap();
+
+    assert_eq!(policies(&mut conn).await.len(), 3);
+
+    conn.delete_password(card.id).await.unwrap();
+    GeneratorProfiles::forget_missing_places(&mut conn)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        policies(&mut conn).await,
+        [policy("checking"), policy("savings")]
+    );
+}
+
+#[async_std::test]
+async fn rotates_passwords_with_the_policy_of_their_place() {
+    let mut conn = vault().await;
+    let key = VaultKey::derive("master", &generate_salt()).unwrap();
+    let mut bank = Password::new("me".to_string(), "bank".to_string(), "1234".to_string());
+    bank.encrypt_password(&key).unwrap();
+    conn.insert_password(&bank).await.unwrap();
+
+    let bank = conn.get_password("bank").await.unwrap().remove(0);
+    let generator = profiles()
+        .pick(Some(&bank.place), None, &GeneratorArgs::default())
+        .unwrap();
+    let rotated = rotate_password(&mut conn, bank, &generator, &key)
+        .await
+        .unwrap();
+
+    assert!(is_pin(&rotated));
+
+    let mut bank = conn.get_password("bank").await.unwrap().remove(0);
+    assert!(bank.is_encrypted());
+    bank.decrypt_password(&key).unwrap();
+    assert_eq!(bank.password, rotated);
+
+    let mut history = conn.get_history(bank.id).await.unwrap();
+    assert_eq!(history.len(), 1);
+    history[0].password.decrypt_password(&key).unwrap();
+    assert_eq!(history[0].password.password, "1234");
+}
+
+/// Profiles saved by older versions lack the options added since.
+#[test]
+fn reads_profiles_missing_options() {
+    let args: GeneratorArgs = serde_json::from_str(r#"{"length": 20}"#).unwrap();
+
+    assert!(
+        args == GeneratorArgs {
+            length: 20,
+            ..GeneratorArgs::default()
+        }
+    );
+}
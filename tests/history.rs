@@ -0,0 +1,110 @@
+mod common;
+
+use common::{password, vault};
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::errors::Error;
+use password_manager::operations::{restore_version, update_with_history};
+use password_manager::password::{CipherVersion, Password};
+use password_manager::utils::{generate_salt, VaultKey};
+
+fn key() -> VaultKey {
+    VaultKey::derive("master", &generate_salt()).unwrap()
+}
+
+async fn stored(conn: &mut DatabaseInterface) -> Password {
+    conn.get_password("mail").await.unwrap().remove(0)
+}
+
+/// Previous passwords of `mail`, oldest first, opened with `key`.
+async fn history(conn: &mut DatabaseInterface, key: &VaultKey) -> Vec<String> {
+    let mail = stored(conn).await;
+    let mut passwords = Vec::new();
+
+    for mut entry in conn.get_history(mail.id).await.unwrap() {
+        assert_eq!(entry.password.cipher, CipherVersion::CURRENT);
+
+        entry.password.decrypt_password(key).unwrap();
+        passwords.push(entry.password.password);
+    }
+
+    passwords
+}
+
+/// Changes the password of `mail` the way `edit` does.
+async fn edit(conn: &mut DatabaseInterface, key: &VaultKey, new_password: &str) {
+    let mut password = stored(conn).await;
+    let was_encrypted = password.is_encrypted();
+    password.decrypt_password(key).unwrap();
+
+    let previous = password.clone();
+
+    password.password = new_password.to_string();
+
+    if was_encrypted {
+        password.encrypt_password(key).unwrap();
+    }
+
+    update_with_history(conn, &password, &previous, key)
+        .await
+        .unwrap();
+}
+
+async fn current(conn: &mut DatabaseInterface, key: &VaultKey) -> Password {
+    let mut password = stored(conn).await;
+    password.decrypt_password(key).unwrap();
+
+    password
+}
+
+#[async_std::test]
+async fn restores_a_previous_password() {
+    for encrypted in [true, false] {
+        let mut conn = vault().await;
+        let key = key();
+        let mut mail = password("mail", "me", "hunter1");
+
+        if encrypted {
+            mail.encrypt_password(&key).unwrap();
+        }
+        conn.insert_password(&mail).await.unwrap();
+
+        edit(&mut conn, &key, "hunter2").await;
+        edit(&mut conn, &key, "hunter3").await;
+
+        assert_eq!(history(&mut conn, &key).await, ["hunter1", "hunter2"]);
+
+        let mail = stored(&mut conn).await;
+        restore_version(&mut conn, mail, 1, &key).await.unwrap();
+
+        let mail = stored(&mut conn).await;
+        assert_eq!(mail.is_encrypted(), encrypted);
+        assert_eq!(current(&mut conn, &key).await.password, "hunter1");
+        assert_eq!(
+            history(&mut conn, &key).await,
+            ["hunter1", "hunter2", "hunter3"]
+        );
+    }
+}
+
+#[async_std::test]
+async fn rejects_versions_out_of_range() {
+    let mut conn = vault().await;
+    let key = key();
+
+    conn.insert_password(&password("mail", "me", "hunter1"))
+        .await
+        .unwrap();
+    edit(&mut conn, &key, "hunter2").await;
+
+    for version in [0, 2] {
+        let mail = stored(&mut conn).await;
+
+        assert!(matches!(
+            restore_version(&mut conn, mail, version, &key).await,
+            Err(Error::NoVersion(v)) if v == version
+        ));
+    }
+
+    assert_eq!(current(&mut conn, &key).await.password, "hunter2");
+    assert_eq!(history(&mut conn, &key).await, ["hunter1"]);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::{password, vault};
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::operations::reseal_legacy_rows;
+use password_manager::password::CipherVersion;
+use password_manager::utils::{decrypt_legacy, generate_salt, VaultKey};
+
+const MASTER_KEY: &str = "legacy master";
+
+/// `hunter2`, `recovery codes` and `hunter1`, sealed by magic-crypt under `MASTER_KEY` the way
+/// versions before 0.5 did.
+const PASSWORD: &str = "R1KKNgL4/FI/BHXmlcTKAA==";
+const NOTES: &str = "UOSfI3qyV6zKdaB2gfMW+g==";
+const PREVIOUS: &str = "KH82N8BoOzGQ7xDcltWXSw==";
+
+fn key() -> VaultKey {
+    VaultKey::derive(MASTER_KEY, &generate_salt()).unwrap()
+}
+
+/// Vault holding a `mail` entry and one previous password, both sealed by magic-crypt, next to a
+/// plaintext `wifi` entry.
+async fn legacy_vault() -> DatabaseInterface {
+    let mut conn = vault().await;
+
+    let mut mail = password("mail", "me", PASSWORD);
+    mail.notes = NOTES.to_string();
+    mail.cipher = CipherVersion::MagicCrypt;
+    conn.insert_password(&mail).await.unwrap();
+    conn.insert_password(&password("wifi", "me", "open"))
+        .await
+        .unwrap();
+
+    let mut previous = conn.get_password("mail").await.unwrap().remove(0);
+    previous.password = PREVIOUS.to_string();
+    previous.notes = String::new();
+    conn.insert_history(&previous).await.unwrap();
+
+    conn
+}
+
+#[test]
+fn opens_magic_crypt_ciphertexts() {
+    let key = key();
+
+    assert_eq!(decrypt_legacy(PASSWORD, &key).unwrap(), "hunter2");
+    assert_eq!(decrypt_legacy(NOTES, &key).unwrap(), "recovery codes");
+    assert_eq!(decrypt_legacy(PREVIOUS, &key).unwrap(), "hunter1");
+
+    let other = VaultKey::derive("other master", &generate_salt()).unwrap();
+    assert!(decrypt_legacy(PASSWORD, &other).is_err());
+}
+
+#[async_std::test]
+async fn reseals_legacy_rows_and_history() {
+    let mut conn = legacy_vault().await;
+    let key = key();
+
+    assert_eq!(reseal_legacy_rows(&mut conn, &key).await.unwrap(), 1);
+
+    let mut mail = conn.get_password("mail").await.unwrap().remove(0);
+    assert_eq!(mail.cipher, CipherVersion::Aes256Gcm);
+    assert_ne!(mail.password, PASSWORD);
+
+    let mut history = conn.get_history(mail.id).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].password.cipher, CipherVersion::Aes256Gcm);
+
+    mail.decrypt_password(&key).unwrap();
+    assert_eq!(mail.password, "hunter2");
+    assert_eq!(mail.notes, "recovery codes");
+
+    history[0].password.decrypt_password(&key).unwrap();
+    assert_eq!(history[0].password.password, "hunter1");
+
+    let wifi = conn.get_password("wifi").await.unwrap().remove(0);
+    assert_eq!(wifi.cipher, CipherVersion::Plaintext);
+    assert_eq!(wifi.password, "open");
+
+    // Nothing is left to migrate on a second run.
+    assert_eq!(reseal_legacy_rows(&mut conn, &key).await.unwrap(), 0);
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeSet;
+use std::fs;
+
+use password_manager::database::migrations::{
+    get_schema_version, latest_version, migrate, KNOWN_TABLES,
+};
+use password_manager::database::objects::ConfigParams;
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::database::utils::{get_save_dir_path, get_save_file_path};
+use password_manager::password::{CipherVersion, EntryKind};
+use sqlx::{Connection, SqliteConnection};
+
+async fn columns(table: &str) -> Vec<String> {
+    let mut connection =
+        SqliteConnection::connect(&get_save_file_path().unwrap().display().to_string())
+            .await
+            .unwrap();
+    let rows: Vec<(String,)> =
+        sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}');", table))
+            .fetch_all(&mut connection)
+            .await
+            .unwrap();
+
+    rows.into_iter().map(|row| row.0).collect()
+}
+
+/// Debug builds keep the save file under the working directory, so this is the only test in this
+/// file: it moves into a directory of its own to build a 0.4 vault and migrate it.
+#[async_std::test]
+async fn migrates_a_legacy_vault_to_the_latest_schema() {
+    let dir = std::env::temp_dir().join(format!("migrations-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    std::env::set_current_dir(&dir).unwrap();
+    fs::create_dir_all(get_save_dir_path().unwrap()).unwrap();
+
+    let mut conn = DatabaseInterface::new().await.unwrap();
+
+    conn.create_config_table().await.unwrap();
+    conn.create_password_table().await.unwrap();
+    conn.execute(
+        "INSERT INTO config (name, value) VALUES ('AccessCheck', 'hash');
+        INSERT INTO passwords (password, username, place, encrypted)
+            VALUES ('sealed', 'bob', 'mail', 1), ('open', NULL, 'wifi', 0);",
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(get_schema_version(&mut conn).await.unwrap(), 0);
+
+    migrate(&mut conn, true).await.unwrap();
+
+    assert_eq!(
+        get_schema_version(&mut conn).await.unwrap(),
+        latest_version()
+    );
+
+    let tables = conn.list_tables().await.unwrap();
+    assert_eq!(
+        tables.iter().map(String::as_str).collect::<BTreeSet<_>>(),
+        KNOWN_TABLES.iter().copied().collect::<BTreeSet<_>>()
+    );
+
+    assert_eq!(
+        columns("passwords").await,
+        [
+            "id",
+            "place",
+            "username",
+            "password",
+            "cipher",
+            "created_at",
+            "updated_at",
+            "last_used_at",
+            "deleted_at",
+            "folder",
+            "notes",
+            "totp",
+            "kind",
+        ]
+    );
+
+    let mut passwords = conn.get_all_passwords().await.unwrap();
+    passwords.sort_by(|a, b| a.place.cmp(&b.place));
+
+    assert_eq!(passwords.len(), 2);
+    assert_eq!(
+        (passwords[0].place.as_str(), passwords[0].username.as_str()),
+        ("mail", "bob")
+    );
+    assert_eq!(passwords[0].password, "sealed");
+    assert_eq!(passwords[0].cipher, CipherVersion::MagicCrypt);
+    assert_eq!(
+        (passwords[1].place.as_str(), passwords[1].username.as_str()),
+        ("wifi", "")
+    );
+    assert_eq!(passwords[1].password, "open");
+    assert_eq!(passwords[1].cipher, CipherVersion::Plaintext);
+
+    for password in passwords {
+        assert_eq!(password.kind, EntryKind::Login);
+        assert!(password.created_at > 0);
+        assert_eq!(password.last_used_at, None);
+        assert_eq!(password.deleted_at, None);
+    }
+
+    assert_eq!(
+        conn.get_setting(ConfigParams::AccessCheck)
+            .await
+            .unwrap()
+            .value,
+        "hash"
+    );
+    assert!(conn.has_setting(ConfigParams::KdfSalt).await.unwrap());
+
+    let backup = get_save_dir_path().unwrap().join("data.v0.sqlite.bak");
+    assert!(backup.exists());
+
+    let mut backup_conn = SqliteConnection::connect(&backup.display().to_string())
+        .await
+        .unwrap();
+    let legacy: Vec<(String, i64)> =
+        sqlx::query_as("SELECT place, encrypted FROM passwords ORDER BY place;")
+            .fetch_all(&mut backup_conn)
+            .await
+            .unwrap();
+    assert_eq!(legacy, [("mail".to_string(), 1), ("wifi".to_string(), 0)]);
+
+    migrate(&mut conn, true).await.unwrap();
+    assert_eq!(
+        fs::read_dir(get_save_dir_path().unwrap()).unwrap().count(),
+        2
+    );
+
+    conn.close().await.unwrap();
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::password;
+use password_manager::errors::Error;
+use password_manager::pass::{read_store, write_store, PlainText};
+use password_manager::password::{CustomField, EntryKind, Password};
+
+/// Empty directory for one test, removed again when dropped.
+struct Store(PathBuf);
+
+impl Store {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("pass-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+
+        Self(dir)
+    }
+
+    fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.0.join(path)).expect("the entry should exist")
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn field(password: &Password, name: &str) -> Option<String> {
+    password
+        .fields
+        .iter()
+        .find(|field| field.name == name)
+        .map(|field| field.value.clone())
+}
+
+#[test]
+fn lays_entries_out_like_pass() {
+    let store = Store::new("layout");
+    let mut db = password("db", "root", "s3cret");
+    db.set_folder("work/servers");
+    db.notes = "rotated quarterly".to_string();
+    db.set_field(CustomField {
+        name: "url".to_string(),
+        value: "https://db.example.com".to_string(),
+        concealed: false,
+    });
+    let passwords = vec![
+        db,
+        password("mail", "alice", "a"),
+        password("mail", "bob", "b"),
+        password("a/b", "x", "slash"),
+        password(".hidden", "x", "dot"),
+    ];
+
+    let skipped = write_store(&store.0, &passwords, &PlainText, &[]).unwrap();
+
+    assert!(skipped.is_empty());
+    assert_eq!(
+        store.read("work/servers/db.txt"),
+        "s3cret\nlogin: root\nurl: https://db.example.com\n\nrotated quarterly\n"
+    );
+    assert_eq!(store.read("mail/alice.txt"), "a\nlogin: alice\n");
+    assert_eq!(store.read("mail/bob.txt"), "b\nlogin: bob\n");
+    assert_eq!(store.read("a-b.txt"), "slash\nlogin: x\n");
+    assert_eq!(store.read("_hidden.txt"), "dot\nlogin: x\n");
+}
+
+#[test]
+fn stores_round_trip() {
+    let store = Store::new("round-trip");
+    let mut card = password("visa", "Jane Doe", "4111111111111111");
+    card.kind = EntryKind::Card;
+    card.set_tags(&["bank", "personal"]).unwrap();
+    card.set_field(CustomField {
+        name: "expiry".to_string(),
+        value: "12/29".to_string(),
+        concealed: true,
+    });
+    card.totp = "otpauth://totp/visa?secret=JBSWY3DPEHPK3PXP".to_string();
+    card.notes = "Call: the bank\nif lost".to_string();
+    let mut note = password("wifi", "", "");
+    note.kind = EntryKind::SecureNote;
+    note.notes = "network: home".to_string();
+    let passwords = vec![
+        card,
+        note,
+        password("mail", "alice", "a"),
+        password("mail", "bob", "b"),
+    ];
+
+    write_store(&store.0, &passwords, &PlainText, &[]).unwrap();
+    let read = read_store(&store.0, &PlainText).unwrap();
+
+    assert_eq!(read.len(), 4);
+
+    let alice = &read[0];
+    assert_eq!(
+        (alice.place.as_str(), alice.username.as_str()),
+        ("mail", "alice")
+    );
+    assert_eq!(alice.folder, "");
+    let bob = &read[1];
+    assert_eq!((bob.place.as_str(), bob.username.as_str()), ("mail", "bob"));
+
+    let card = &read[2];
+    assert_eq!(card.place, "visa");
+    assert_eq!(card.username, "Jane Doe");
+    assert_eq!(card.password, "4111111111111111");
+    assert_eq!(card.kind, EntryKind::Card);
+    assert_eq!(card.tags, vec!["bank", "personal"]);
+    assert_eq!(field(card, "expiry").as_deref(), Some("12/29"));
+    assert_eq!(card.totp, "otpauth://totp/visa?secret=JBSWY3DPEHPK3PXP");
+    assert_eq!(card.notes, "Call: the bank\nif lost");
+
+    let note = &read[3];
+    assert_eq!(note.kind, EntryKind::SecureNote);
+    assert_eq!(note.password, "");
+    assert_eq!(note.notes, "network: home");
+}
+
+#[test]
+fn reads_stores_written_by_other_tools() {
+    let store = Store::new("foreign");
+    fs::create_dir_all(store.0.join("Email/.git")).unwrap();
+    fs::write(store.0.join(".gpg-id"), "me@example.com\n").unwrap();
+    fs::write(store.0.join("Email/.git/config"), "[core]\n").unwrap();
+    fs::write(store.0.join("Email/notes.md"), "not an entry").unwrap();
+    fs::write(
+        store.0.join("Email/gmail.txt"),
+        "hunter2\nUsername: alice@gmail.com\nURL: https://mail.google.com\nRecovery codes below\n1234\n",
+    )
+    .unwrap();
+
+    let read = read_store(&store.0, &PlainText).unwrap();
+
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].place, "gmail");
+    assert_eq!(read[0].folder, "Email");
+    assert_eq!(read[0].username, "alice@gmail.com");
+    assert_eq!(read[0].password, "hunter2");
+    assert_eq!(
+        field(&read[0], "url").as_deref(),
+        Some("https://mail.google.com")
+    );
+    assert_eq!(read[0].notes, "Recovery codes below\n1234");
+}
+
+#[test]
+fn leaves_out_multiline_passwords() {
+    let store = Store::new("multiline");
+    let passwords = vec![
+        password(
+            "laptop",
+            "me@host",
+            "-----BEGIN KEY-----\nabc\n-----END KEY-----",
+        ),
+        password("db", "root", "s3cret"),
+    ];
+
+    let skipped = write_store(&store.0, &passwords, &PlainText, &[]).unwrap();
+
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].place, "laptop");
+    assert!(!store.0.join("laptop.txt").exists());
+}
+
+#[test]
+fn refuses_to_write_into_a_non_empty_directory() {
+    let store = Store::new("non-empty");
+    fs::create_dir_all(&store.0).unwrap();
+    fs::write(store.0.join("existing.txt"), "x").unwrap();
+
+    let result = write_store(&store.0, &[password("db", "root", "x")], &PlainText, &[]);
+
+    assert!(matches!(result, Err(Error::DirectoryNotEmpty(_))));
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use common::{password, vault};
+use password_manager::database::objects::ConfigParams;
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::database::utils::{get_kdf_salt, replace_key};
+use password_manager::operations::rekey_vault;
+use password_manager::password::{CipherVersion, Password};
+use password_manager::utils::{generate_salt, VaultKey};
+
+const TOTP: &str = "otpauth://totp/wifi:me?secret=JBSWY3DPEHPK3PXP";
+
+/// Vault under the master key `old`: an encrypted `mail` entry with a notes field and one
+/// previous password, and a plaintext `wifi` entry whose TOTP secret is sealed.
+async fn stocked_vault() -> (DatabaseInterface, VaultKey) {
+    let mut conn = vault().await;
+    let salt = generate_salt();
+    let key = VaultKey::derive("old", &salt).unwrap();
+
+    replace_key("old", &salt, &mut conn).await.unwrap();
+
+    let mut mail = password("mail", "me", "hunter2");
+    mail.notes = "recovery codes".to_string();
+    mail.encrypt_password(&key).unwrap();
+    conn.insert_password(&mail).await.unwrap();
+
+    let mut wifi = password("wifi", "me", "open");
+    wifi.totp = TOTP.to_string();
+    wifi.seal_totp(&key).unwrap();
+    conn.insert_password(&wifi).await.unwrap();
+
+    let mut previous = conn.get_password("mail").await.unwrap().remove(0);
+    previous.password = "hunter1".to_string();
+    previous.cipher = CipherVersion::Plaintext;
+    previous.encrypt_password(&key).unwrap();
+    conn.insert_history(&previous).await.unwrap();
+
+    (conn, key)
+}
+
+async fn sorted_passwords(conn: &mut DatabaseInterface) -> Vec<Password> {
+    let mut passwords = conn.get_all_passwords().await.unwrap();
+    passwords.sort_by(|a, b| a.place.cmp(&b.place));
+
+    passwords
+}
+
+/// Passwords, notes, TOTP secrets and previous passwords, opened with `key`.
+async fn open_with(conn: &mut DatabaseInterface, key: &VaultKey) -> Option<Vec<String>> {
+    let mut opened = Vec::new();
+
+    for mut password in sorted_passwords(conn).await {
+        for mut entry in conn.get_history(password.id).await.unwrap() {
+            entry.password.decrypt_password(key).ok()?;
+            opened.push(entry.password.password);
+        }
+
+        password.decrypt_password(key).ok()?;
+        opened.extend([password.password, password.notes, password.totp]);
+    }
+
+    Some(opened)
+}
+
+fn expected() -> Vec<String> {
+    ["hunter1", "hunter2", "recovery codes", "", "open", "", TOTP]
+        .map(String::from)
+        .to_vec()
+}
+
+#[async_std::test]
+async fn reseals_rows_history_and_totp_under_the_new_key() {
+    let (mut conn, old_key) = stocked_vault().await;
+    let new_salt = generate_salt();
+    let new_key = VaultKey::derive("new", &new_salt).unwrap();
+
+    assert_eq!(open_with(&mut conn, &old_key).await, Some(expected()));
+
+    let rekeyed = rekey_vault(&mut conn, &old_key, &new_key, "new", &new_salt)
+        .await
+        .unwrap();
+
+    assert_eq!(rekeyed, 2);
+    assert_eq!(open_with(&mut conn, &new_key).await, Some(expected()));
+    assert_eq!(open_with(&mut conn, &old_key).await, None);
+
+    let passwords = sorted_passwords(&mut conn).await;
+    assert_eq!(passwords[0].cipher, CipherVersion::CURRENT);
+    assert_eq!(passwords[1].cipher, CipherVersion::Plaintext);
+    assert_eq!(passwords[1].password, "open");
+
+    let access_check = conn.get_setting(ConfigParams::AccessCheck).await.unwrap();
+    assert!(bcrypt::verify("new", &access_check.value).unwrap());
+    assert!(!bcrypt::verify("old", &access_check.value).unwrap());
+    assert_eq!(get_kdf_salt(&mut conn).await.unwrap(), new_salt);
+}
+
+/// Runs `rekey_vault` in a transaction rolled back on failure, as `change-key` does, and checks
+/// that the vault still opens with the old key only.
+async fn assert_rolled_back(conn: &mut DatabaseInterface, old_key: &VaultKey) {
+    let old_salt = get_kdf_salt(conn).await.unwrap();
+    let new_salt = generate_salt();
+    let new_key = VaultKey::derive("new", &new_salt).unwrap();
+
+    conn.begin().await.unwrap();
+    let result = rekey_vault(conn, old_key, &new_key, "new", &new_salt).await;
+    assert!(result.is_err());
+    conn.rollback().await.unwrap();
+
+    conn.execute("DELETE FROM passwords WHERE place = 'bank';")
+        .await
+        .unwrap();
+
+    assert_eq!(open_with(conn, old_key).await, Some(expected()));
+    assert_eq!(open_with(conn, &new_key).await, None);
+
+    let access_check = conn.get_setting(ConfigParams::AccessCheck).await.unwrap();
+    assert!(bcrypt::verify("old", &access_check.value).unwrap());
+    assert_eq!(get_kdf_salt(conn).await.unwrap(), old_salt);
+}
+
+#[async_std::test]
+async fn rolls_back_when_a_row_does_not_open() {
+    let (mut conn, old_key) = stocked_vault().await;
+
+    let mut corrupt = password("bank", "me", "bm90IGEgY2lwaGVydGV4dCBhdCBhbGw=");
+    corrupt.cipher = CipherVersion::CURRENT;
+    conn.insert_password(&corrupt).await.unwrap();
+
+    assert_rolled_back(&mut conn, &old_key).await;
+}
+
+#[async_std::test]
+async fn rolls_back_when_a_write_fails() {
+    let (mut conn, old_key) = stocked_vault().await;
+
+    // The passwords are rewritten by the time the history fails to update.
+    conn.execute(
+        "CREATE TRIGGER fail_history BEFORE UPDATE ON password_history
+            BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+    )
+    .await
+    .unwrap();
+
+    assert_rolled_back(&mut conn, &old_key).await;
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use password_manager::database::snapshots::{list_snapshots_in, prune_snapshots, SnapshotPolicy};
+
+/// Empty directory of its own for each test, holding a file per snapshot id.
+fn backups(name: &str, ids: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("snapshots-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    for id in ids {
+        fs::write(dir.join(format!("data-{}.sqlite", id)), "").unwrap();
+    }
+
+    dir
+}
+
+fn ids(dir: &Path) -> Vec<String> {
+    list_snapshots_in(dir)
+        .unwrap()
+        .into_iter()
+        .map(|snapshot| snapshot.id)
+        .collect()
+}
+
+fn at(time: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+}
+
+fn policy(keep_last: usize, keep_daily: usize) -> SnapshotPolicy {
+    SnapshotPolicy {
+        enabled: true,
+        keep_last,
+        keep_daily,
+    }
+}
+
+#[test]
+fn lists_snapshots_newest_first() {
+    let dir = backups(
+        "list",
+        &[
+            "20261016-090000",
+            "20261018-120000-2",
+            "20261018-120000",
+            "20261018-120000-10",
+            "20261018-120000-1",
+        ],
+    );
+    fs::write(dir.join("data-latest.sqlite"), "").unwrap();
+    fs::write(dir.join("notes.txt"), "").unwrap();
+
+    assert_eq!(
+        ids(&dir),
+        [
+            "20261018-120000-10",
+            "20261018-120000-2",
+            "20261018-120000-1",
+            "20261018-120000",
+            "20261016-090000",
+        ]
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn keeps_the_most_recent_snapshots() {
+    let dir = backups(
+        "last",
+        &[
+            "20261018-090000",
+            "20261018-100000",
+            "20261018-110000",
+            "20261018-120000",
+        ],
+    );
+
+    prune_snapshots(&dir, &policy(2, 0), at("2026-10-18 12:30:00")).unwrap();
+
+    assert_eq!(ids(&dir), ["20261018-120000", "20261018-110000"]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn keeps_the_last_snapshot_of_recent_days() {
+    let dir = backups(
+        "daily",
+        &[
+            "20261014-230000",
+            "20261015-080000",
+            "20261016-080000",
+            "20261016-200000",
+            "20261017-100000",
+            "20261018-090000",
+            "20261018-120000",
+        ],
+    );
+
+    prune_snapshots(&dir, &policy(1, 3), at("2026-10-18 12:30:00")).unwrap();
+
+    assert_eq!(
+        ids(&dir),
+        ["20261018-120000", "20261017-100000", "20261016-200000"]
+    );
+
+    // A day later, the 16th falls out of the window and the 19th has no snapshot yet.
+    prune_snapshots(&dir, &policy(1, 3), at("2026-10-19 08:00:00")).unwrap();
+
+    assert_eq!(ids(&dir), ["20261018-120000", "20261017-100000"]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn prunes_snapshots_taken_in_the_same_second() {
+    let dir = backups(
+        "second",
+        &["20261018-120000", "20261018-120000-1", "20261018-120000-2"],
+    );
+
+    prune_snapshots(&dir, &policy(1, 1), at("2026-10-18 12:00:00")).unwrap();
+
+    assert_eq!(ids(&dir), ["20261018-120000-2"]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
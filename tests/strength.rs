@@ -0,0 +1,52 @@
+use password_manager::password::{
+    estimate_strength, Generator, PassphraseOptions, PasswordBuildOptions, Pattern, Strength,
+};
+
+#[test]
+fn sees_through_common_patterns() {
+    let common = estimate_strength("password123", &[]);
+    assert_eq!(common.score, 0);
+    assert!(common.warning.is_some());
+
+    let from_inputs = estimate_strength("acmealice", &["acme", "alice"]);
+    assert!(from_inputs.score <= 1);
+
+    let random = estimate_strength("qX7#vR2!mP9$wL4@", &[]);
+    assert_eq!(random.score, 4);
+    assert_eq!(random.crack_time(), "centuries");
+}
+
+#[test]
+fn measures_generators_by_their_choices() {
+    let pattern = Generator::Pattern(Pattern::parse("id-9{4}", &[]).unwrap());
+    assert!((pattern.entropy() - 4.0 * 10_f64.log2()).abs() < 1e-9);
+
+    let characters = Generator::Characters(PasswordBuildOptions {
+        length: 10,
+        use_special: false,
+        use_numbers: true,
+        use_upper: false,
+        exclude_char: Vec::new(),
+        min_upper: 0,
+        min_digits: 0,
+        min_special: 0,
+    });
+    assert!((characters.entropy() - 10.0 * 36_f64.log2()).abs() < 1e-9);
+
+    let options = PassphraseOptions::new(3, "-".to_string(), false, false, "a\nb\nc\nd").unwrap();
+    assert!((Generator::Passphrase(options).entropy() - 6.0).abs() < 1e-9);
+}
+
+#[test]
+fn scores_entropy_like_zxcvbn() {
+    assert_eq!(Strength::from_entropy(8.0).score, 0);
+    assert_eq!(Strength::from_entropy(20.0).score, 1);
+    assert_eq!(Strength::from_entropy(25.0).score, 2);
+    assert_eq!(Strength::from_entropy(34.0).score, 3);
+    assert_eq!(Strength::from_entropy(80.0).score, 4);
+    assert_eq!(
+        Strength::from_entropy(8.0).crack_time(),
+        "less than a second"
+    );
+    assert_eq!(Strength::from_entropy(30.0).crack_time(), "15 hours");
+}
@@ -0,0 +1,136 @@
+use base32::Alphabet;
+use password_manager::errors::Error;
+use password_manager::password::{CipherVersion, Password};
+use password_manager::totp::{Totp, TotpAlgorithm};
+use password_manager::utils::{generate_salt, VaultKey};
+
+/// Seeds of RFC 6238 Appendix B, one per algorithm.
+fn rfc_totp(algorithm: TotpAlgorithm) -> Totp {
+    let seed: &[u8] = match algorithm {
+        TotpAlgorithm::Sha1 => b"12345678901234567890",
+        TotpAlgorithm::Sha256 => b"12345678901234567890123456789012",
+        TotpAlgorithm::Sha512 => {
+            b"1234567890123456789012345678901234567890123456789012345678901234"
+        }
+    };
+    let secret = base32::encode(Alphabet::Rfc4648 { padding: false }, seed);
+
+    Totp::new(&secret, algorithm, 8, 30, "rfc".to_string()).unwrap()
+}
+
+/// Test vectors of RFC 6238 Appendix B: time, then the SHA1, SHA256 and SHA512 codes.
+const RFC_VECTORS: [(u64, &str, &str, &str); 6] = [
+    (59, "94287082", "46119246", "90693936"),
+    (1111111109, "07081804", "68084774", "25091201"),
+    (1111111111, "14050471", "67062674", "99943326"),
+    (1234567890, "89005924", "91819424", "93441116"),
+    (2000000000, "69279037", "90698825", "38618901"),
+    (20000000000, "65353130", "77737706", "47863826"),
+];
+
+#[test]
+fn matches_the_rfc_6238_vectors() {
+    let sha1 = rfc_totp(TotpAlgorithm::Sha1);
+    let sha256 = rfc_totp(TotpAlgorithm::Sha256);
+    let sha512 = rfc_totp(TotpAlgorithm::Sha512);
+
+    for (time, sha1_code, sha256_code, sha512_code) in RFC_VECTORS {
+        assert_eq!(sha1.code_at(time), sha1_code, "SHA1 at {}", time);
+        assert_eq!(sha256.code_at(time), sha256_code, "SHA256 at {}", time);
+        assert_eq!(sha512.code_at(time), sha512_code, "SHA512 at {}", time);
+    }
+}
+
+#[test]
+fn counts_down_to_the_next_code() {
+    let totp = rfc_totp(TotpAlgorithm::Sha1);
+
+    assert_eq!(totp.remaining_at(59), 1);
+    assert_eq!(totp.remaining_at(60), 30);
+}
+
+#[test]
+fn round_trips_through_a_uri() {
+    for algorithm in [
+        TotpAlgorithm::Sha1,
+        TotpAlgorithm::Sha256,
+        TotpAlgorithm::Sha512,
+    ] {
+        let mut totp = rfc_totp(algorithm);
+        totp.label = "Example Co:alice+work@example.com".to_string();
+        totp.period = 60;
+
+        let uri = totp.to_uri().unwrap();
+
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert_eq!(Totp::from_uri(&uri).unwrap(), totp);
+    }
+}
+
+#[test]
+fn reads_uris_with_default_parameters() {
+    let totp = Totp::from_uri("otpauth://totp/Site:me?secret=JBSWY3DPEHPK3PXP").unwrap();
+
+    assert_eq!(totp.label, "Site:me");
+    assert_eq!(totp.secret, b"Hello!\xde\xad\xbe\xef");
+    assert_eq!(totp.algorithm, TotpAlgorithm::Sha1);
+    assert_eq!((totp.digits, totp.period), (6, 30));
+}
+
+#[test]
+fn rejects_malformed_uris() {
+    for uri in [
+        "not a uri",
+        "https://totp/Site?secret=JBSWY3DPEHPK3PXP",
+        "otpauth://hotp/Site?secret=JBSWY3DPEHPK3PXP",
+        "otpauth://totp/Site",
+        "otpauth://totp/Site?secret=",
+        "otpauth://totp/Site?secret=not*base32",
+        "otpauth://totp/Site?secret=JBSWY3DPEHPK3PXP&algorithm=MD5",
+        "otpauth://totp/Site?secret=JBSWY3DPEHPK3PXP&digits=4",
+        "otpauth://totp/Site?secret=JBSWY3DPEHPK3PXP&period=0",
+    ] {
+        assert!(
+            matches!(Totp::from_uri(uri), Err(Error::BadTotp(_))),
+            "{} should be rejected",
+            uri
+        );
+    }
+
+    for uri in [
+        "otpauth://totp/Site?secret=JBSWY3DPEHPK3PXP&digits=six",
+        "otpauth://totp/Site?secret=JBSWY3DPEHPK3PXP&period=-30",
+    ] {
+        assert!(
+            matches!(Totp::from_uri(uri), Err(Error::ParsingError)),
+            "{} should be rejected",
+            uri
+        );
+    }
+}
+
+#[test]
+fn seals_the_secret_of_unencrypted_entries() {
+    let key = VaultKey::derive("key", &generate_salt()).unwrap();
+    let uri = rfc_totp(TotpAlgorithm::Sha1).to_uri().unwrap();
+    let mut password = Password::new("me".to_string(), "site".to_string(), "open".to_string());
+    password.totp = uri.clone();
+
+    password.seal_totp(&key).unwrap();
+
+    assert_eq!(password.cipher, CipherVersion::Plaintext);
+    assert_eq!(password.password, "open");
+    assert_ne!(password.totp, uri);
+    assert!(password.is_sealed());
+
+    let new_key = VaultKey::derive("new key", &generate_salt()).unwrap();
+    password.reseal(&key, &new_key).unwrap();
+
+    assert_eq!(password.cipher, CipherVersion::Plaintext);
+    assert!(password.clone().decrypt_password(&key).is_err());
+
+    password.decrypt_password(&new_key).unwrap();
+
+    assert_eq!(password.totp, uri);
+    assert!(!password.is_sealed());
+}
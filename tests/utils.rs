@@ -0,0 +1,127 @@
+use base64::prelude::{Engine, BASE64_STANDARD};
+use password_manager::consts::NONCE_LENGTH;
+use password_manager::errors::Error;
+use password_manager::utils::{decrypt, encrypt, generate_salt, parse_duration, VaultKey};
+
+#[test]
+fn parses_every_unit() {
+    assert_eq!(parse_duration("45s").unwrap(), 45);
+    assert_eq!(parse_duration("30m").unwrap(), 30 * 60);
+    assert_eq!(parse_duration("12h").unwrap(), 12 * 60 * 60);
+    assert_eq!(parse_duration("30d").unwrap(), 30 * 24 * 60 * 60);
+    assert_eq!(parse_duration("2w").unwrap(), 2 * 7 * 24 * 60 * 60);
+}
+
+#[test]
+fn rejects_malformed_durations() {
+    for input in ["", "d", "30", "30y", "3.5d", "d30", "30é", "é"] {
+        assert!(
+            matches!(parse_duration(input), Err(Error::BadInput(_))),
+            "{} should be rejected",
+            input
+        );
+    }
+}
+
+#[test]
+fn rejects_amounts_that_are_not_positive() {
+    for input in ["0d", "-1d", "-30s"] {
+        assert!(
+            matches!(parse_duration(input), Err(Error::BadInput(_))),
+            "{} should be rejected",
+            input
+        );
+    }
+}
+
+#[test]
+fn rejects_durations_that_overflow() {
+    assert!(matches!(
+        parse_duration("9999999999999999d"),
+        Err(Error::ParsingError)
+    ));
+    assert_eq!(parse_duration(&format!("{}s", i64::MAX)).unwrap(), i64::MAX);
+}
+
+fn sealed_bytes(ciphertext: &str) -> Vec<u8> {
+    BASE64_STANDARD.decode(ciphertext).unwrap()
+}
+
+#[test]
+fn opens_what_it_seals() {
+    let key = VaultKey::derive("master", &generate_salt()).unwrap();
+
+    for plaintext in ["hunter2", "", "multi\nline, \"quoted\" ünïcode"] {
+        let ciphertext = encrypt(plaintext, &key).unwrap();
+
+        assert!(!ciphertext.contains("hunter2"));
+        assert_eq!(decrypt(&ciphertext, &key).unwrap(), plaintext);
+    }
+
+    // A fresh nonce per seal keeps equal passwords from looking equal.
+    assert_ne!(
+        encrypt("hunter2", &key).unwrap(),
+        encrypt("hunter2", &key).unwrap()
+    );
+}
+
+#[test]
+fn detects_tampered_ciphertexts() {
+    let key = VaultKey::derive("master", &generate_salt()).unwrap();
+    let sealed = sealed_bytes(&encrypt("hunter2", &key).unwrap());
+
+    for index in [0, sealed.len() / 2, sealed.len() - 1] {
+        let mut tampered = sealed.clone();
+        tampered[index] ^= 1;
+
+        assert!(matches!(
+            decrypt(&BASE64_STANDARD.encode(tampered), &key),
+            Err(Error::TamperedCiphertext)
+        ));
+    }
+
+    // Cut past the nonce, the authentication tag no longer matches.
+    assert!(matches!(
+        decrypt(&BASE64_STANDARD.encode(&sealed[..sealed.len() - 1]), &key),
+        Err(Error::TamperedCiphertext)
+    ));
+}
+
+#[test]
+fn rejects_malformed_ciphertexts() {
+    let key = VaultKey::derive("master", &generate_salt()).unwrap();
+    let sealed = sealed_bytes(&encrypt("hunter2", &key).unwrap());
+
+    for malformed in [
+        String::new(),
+        BASE64_STANDARD.encode(&sealed[..NONCE_LENGTH - 1]),
+        "not base64!".to_string(),
+    ] {
+        assert!(
+            matches!(decrypt(&malformed, &key), Err(Error::MalformedCiphertext)),
+            "{:?} should be rejected",
+            malformed
+        );
+    }
+}
+
+#[test]
+fn needs_the_same_key_and_salt_to_open() {
+    let salt = generate_salt();
+    let key = VaultKey::derive("master", &salt).unwrap();
+    let ciphertext = encrypt("hunter2", &key).unwrap();
+
+    let wrong_key = VaultKey::derive("masters", &salt).unwrap();
+    let wrong_salt = VaultKey::derive("master", &generate_salt()).unwrap();
+
+    for other in [&wrong_key, &wrong_salt] {
+        assert!(matches!(
+            decrypt(&ciphertext, other),
+            Err(Error::TamperedCiphertext)
+        ));
+    }
+
+    let same = key.with_salt(&salt).unwrap();
+    assert_eq!(decrypt(&ciphertext, &same).unwrap(), "hunter2");
+    assert!(decrypt(&ciphertext, &key.with_salt(&generate_salt()).unwrap()).is_err());
+}
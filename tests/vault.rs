@@ -0,0 +1,188 @@
+mod common;
+
+use common::{password, vault};
+use password_manager::database::queries::DatabaseInterface;
+use password_manager::errors::Error;
+use password_manager::operations::check_free;
+use password_manager::password::{sort_passwords, Password, SortBy};
+
+async fn insert(conn: &mut DatabaseInterface, passwords: &[Password]) {
+    for password in passwords {
+        conn.insert_password(password).await.unwrap();
+    }
+}
+
+fn no_choice(_: &[String]) -> Result<usize, Error> {
+    panic!("there should be a single account to pick")
+}
+
+#[async_std::test]
+async fn keeps_several_accounts_per_place() {
+    let mut conn = vault().await;
+    insert(
+        &mut conn,
+        &[
+            password("mail", "alice", "a1"),
+            password("mail", "bob", "b1"),
+            password("shop", "alice", "s1"),
+        ],
+    )
+    .await;
+
+    assert_eq!(conn.get_password("mail").await.unwrap().len(), 2);
+    assert!(conn
+        .insert_password(&password("mail", "bob", "b2"))
+        .await
+        .is_err());
+}
+
+#[async_std::test]
+async fn points_to_the_trash_when_adding_a_trashed_account_again() {
+    let mut conn = vault().await;
+    insert(&mut conn, &[password("mail", "bob", "b1")]).await;
+
+    let again = password("mail", "bob", "b2");
+    assert!(matches!(
+        check_free(&mut conn, &again).await,
+        Err(Error::PasswordExists(summary)) if summary == "mail (bob)"
+    ));
+
+    let mail = conn.get_password("mail").await.unwrap().remove(0);
+    conn.trash_password(mail.id).await.unwrap();
+
+    assert!(matches!(
+        check_free(&mut conn, &again).await,
+        Err(Error::PasswordInTrash(summary)) if summary == "mail (bob)"
+    ));
+    assert!(check_free(&mut conn, &password("mail", "alice", "a1"))
+        .await
+        .is_ok());
+
+    conn.delete_password(mail.id).await.unwrap();
+
+    check_free(&mut conn, &again).await.unwrap();
+    conn.insert_password(&again).await.unwrap();
+}
+
+#[async_std::test]
+async fn asks_which_account_to_use() {
+    let mut conn = vault().await;
+    insert(
+        &mut conn,
+        &[
+            password("mail", "alice", "a1"),
+            password("mail", "bob", "b1"),
+        ],
+    )
+    .await;
+
+    let accounts = conn.get_password("mail").await.unwrap();
+    let picked = Password::pick("mail".to_string(), None, accounts, |usernames| {
+        assert_eq!(usernames, ["alice", "bob"]);
+
+        Ok(1)
+    })
+    .unwrap();
+
+    assert_eq!(
+        (picked.username.as_str(), picked.password.as_str()),
+        ("bob", "b1")
+    );
+}
+
+#[async_std::test]
+async fn picks_the_account_named_by_username() {
+    let mut conn = vault().await;
+    insert(
+        &mut conn,
+        &[
+            password("mail", "alice", "a1"),
+            password("mail", "bob", "b1"),
+            password("shop", "alice", "s1"),
+        ],
+    )
+    .await;
+
+    let accounts = conn.get_password("mail").await.unwrap();
+    let picked = Password::pick(
+        "mail".to_string(),
+        Some("alice".to_string()),
+        accounts,
+        no_choice,
+    )
+    .unwrap();
+    assert_eq!(picked.password, "a1");
+
+    let accounts = conn.get_password("shop").await.unwrap();
+    let picked = Password::pick("shop".to_string(), None, accounts, no_choice).unwrap();
+    assert_eq!(picked.password, "s1");
+
+    let accounts = conn.get_password("mail").await.unwrap();
+    let missing = Password::pick(
+        "mail".to_string(),
+        Some("carol".to_string()),
+        accounts,
+        no_choice,
+    );
+    assert!(matches!(missing, Err(Error::NoPassword(place)) if place == "mail"));
+}
+
+fn places(passwords: &[Password]) -> Vec<&str> {
+    passwords
+        .iter()
+        .map(|password| password.place.as_str())
+        .collect()
+}
+
+#[async_std::test]
+async fn touching_records_the_last_use() {
+    let mut conn = vault().await;
+    insert(
+        &mut conn,
+        &[password("mail", "me", "m1"), password("shop", "me", "s1")],
+    )
+    .await;
+
+    let mail = Password::from("mail".to_string(), None, &mut conn)
+        .await
+        .unwrap();
+    conn.touch_password(mail.id).await.unwrap();
+
+    let passwords = conn.get_active_passwords().await.unwrap();
+    let mail = passwords.iter().find(|password| password.place == "mail");
+    let shop = passwords.iter().find(|password| password.place == "shop");
+
+    assert!(mail.unwrap().last_used_at.is_some_and(|time| time > 0));
+    assert_eq!(shop.unwrap().last_used_at, None);
+    assert_eq!(shop.unwrap().timestamp(SortBy::LastUsed), None);
+}
+
+#[async_std::test]
+async fn sorts_by_last_use_with_never_used_first() {
+    let mut conn = vault().await;
+    insert(
+        &mut conn,
+        &[
+            password("a", "me", "1"),
+            password("b", "me", "2"),
+            password("c", "me", "3"),
+            password("d", "me", "4"),
+        ],
+    )
+    .await;
+    conn.execute(
+        "UPDATE passwords SET last_used_at = 300 WHERE place = 'a';
+        UPDATE passwords SET last_used_at = 100 WHERE place = 'c';
+        UPDATE passwords SET last_used_at = 200 WHERE place = 'd';",
+    )
+    .await
+    .unwrap();
+
+    let mut passwords = conn.get_active_passwords().await.unwrap();
+
+    sort_passwords(&mut passwords, SortBy::LastUsed);
+    assert_eq!(places(&passwords), ["b", "c", "d", "a"]);
+
+    sort_passwords(&mut passwords, SortBy::Place);
+    assert_eq!(places(&passwords), ["a", "b", "c", "d"]);
+}